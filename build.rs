@@ -1,6 +1,7 @@
 use std::env;
 
 pub fn main() {
+    println!("cargo::rustc-check-cfg=cfg(build, values(\"debug\", \"release\"))");
     if let Ok(profile) = env::var("PROFILE") {
         println!("cargo:rustc-cfg=build={:?}", profile);
     }
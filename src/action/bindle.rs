@@ -1,9 +1,15 @@
 use colored::Colorize;
 
-use crate::tool::{fs::AppDir, global_input::GlobalInput, kaleido, logger::error};
+use crate::tool::{
+    fs::AppDir,
+    global_input::GlobalInput,
+    kaleido::{self, Channel},
+    logger::error,
+};
 
 use super::helper::get_installed_packages;
 use super::installer::{install as install_packages, InstallRequest, InstallerContext};
+use super::pin::skip_pinned;
 use super::uninstall::uninstall as uninstall_packages;
 
 pub async fn list(name: Option<String>, app_dir: &AppDir) {
@@ -50,7 +56,7 @@ pub async fn list_packages(name: String, app_dir: &AppDir) {
                 println!(
                     "* {} - {}",
                     request.name.green(),
-                    installed_package.version.green()
+                    installed_package.display_version().green()
                 );
             }
             None => {
@@ -113,7 +119,20 @@ pub async fn update(
     global_input: &mut GlobalInput<'_>,
     context: &InstallerContext,
 ) {
-    install(name, app_dir, global_input, context).await;
+    let (requests, missing) = match get_packages(&name, app_dir) {
+        Some(v) => v,
+        None => return,
+    };
+    let requests = skip_pinned(requests, app_dir, context.force);
+    install_packages(requests, app_dir, global_input, context).await;
+
+    if !missing.is_empty() {
+        println!();
+        println!("cannot find the following packages:");
+        for name in missing {
+            println!("{}", name.red());
+        }
+    }
 }
 
 fn get_packages(name: &str, app_dir: &AppDir) -> Option<(Vec<InstallRequest>, Vec<String>)> {
@@ -142,7 +161,8 @@ fn get_packages(name: &str, app_dir: &AppDir) -> Option<(Vec<InstallRequest>, Ve
         {
             requests.push(InstallRequest {
                 name: p.name.clone(),
-                version: None,
+                version: bindle_package.version.clone(),
+                prerelease: bindle_package.channel == Some(Channel::Prerelease),
                 alias: bindle_package.alias.clone(),
             })
         } else {
@@ -0,0 +1,45 @@
+use colored::Colorize;
+use indicatif::HumanBytes;
+
+use crate::tool::{cache::DownloadCache, fs::AppDir, logger::error};
+
+/// list cached files, the least recently used one first.
+pub async fn list(app_dir: &AppDir) {
+    let index = match DownloadCache::open(app_dir).load_index() {
+        Ok(i) => i,
+        Err(e) => {
+            error!("Failed to load cache index: {}", e);
+            return;
+        }
+    };
+    let mut entries: Vec<_> = index.entries.iter().collect();
+    entries.sort_by_key(|(_, e)| e.last_used);
+    for (key, entry) in entries {
+        println!(
+            "* {} ({}) - {}",
+            entry.file_name.green(),
+            HumanBytes(entry.size),
+            key
+        );
+    }
+}
+
+pub async fn clean(app_dir: &AppDir) {
+    match DownloadCache::open(app_dir).clean() {
+        Ok(size) => println!("{} freed", HumanBytes(size)),
+        Err(e) => println!("{}", format!("failed to clean cache: {}", e).red()),
+    }
+}
+
+pub async fn size(app_dir: &AppDir) {
+    let cache = DownloadCache::open(app_dir);
+    match cache.load_index() {
+        Ok(index) => println!(
+            "{} files, {} of {}",
+            index.file_count(),
+            HumanBytes(index.total_size()),
+            HumanBytes(cache.max_size())
+        ),
+        Err(e) => error!("Failed to load cache index: {}", e),
+    }
+}
@@ -39,6 +39,19 @@ pub async fn update(home_dir: &Path) {
     }
 }
 
+/// true if the config file is missing or older than 7 days, so `check_config` may change it.
+pub fn is_config_stale(home_dir: &Path) -> bool {
+    let config_path = home_dir.join(KALEIDO_SYS_FILE);
+    if !config_path.is_file() {
+        return true;
+    }
+    std::fs::metadata(&config_path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| SystemTime::now().duration_since(t).ok())
+        .is_some_and(|d| d > Duration::from_secs(DAYS_7_IN_SECS))
+}
+
 pub async fn check_config(home_dir: &Path, global_input: &mut GlobalInput<'_>) {
     let config_path = home_dir.join(KALEIDO_SYS_FILE);
     debug!("check config file");
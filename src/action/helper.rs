@@ -1,16 +1,22 @@
 use std::{
     collections::HashMap,
-    io::{self, BufRead, Cursor, Error, Result},
+    io::{Error, Result},
     path::{Path, PathBuf},
+};
+#[cfg(target_os = "windows")]
+use std::{
+    io::{self, BufRead, Cursor},
     str,
 };
 
 use tokio::fs::read_dir;
 
+use super::installer::normalize_version;
 use crate::tool::{
     fs::AppDir,
     kaleido::{self, Package},
     logger::{debug, error},
+    state::{is_migrated, mark_migrated, unix_now, PackageReceipts, Receipt},
 };
 
 #[derive(Debug, Clone)]
@@ -19,7 +25,176 @@ pub struct InstalledPackage {
     pub version: String,
 }
 
+impl InstalledPackage {
+    /// true if the version has a semver prerelease part, e.g. '1.0.0-rc.1', other versions are never prereleases.
+    pub fn is_prerelease(&self) -> bool {
+        semver::Version::parse(normalize_version(&self.version))
+            .map(|v| !v.pre.is_empty())
+            .unwrap_or(false)
+    }
+
+    /// the version with a mark for prereleases.
+    pub fn display_version(&self) -> String {
+        if self.is_prerelease() {
+            format!("{} (prerelease)", self.version)
+        } else {
+            self.version.clone()
+        }
+    }
+}
+
+/// current versions of installed packages, read from install receipts.
 pub async fn get_installed_packages(app_dir: &AppDir) -> Result<Vec<InstalledPackage>> {
+    let receipts = load_receipts(app_dir).await?;
+    Ok(receipts
+        .into_iter()
+        .filter_map(|(package, r)| {
+            r.current.map(|version| InstalledPackage { package, version })
+        })
+        .collect())
+}
+
+/// aliases and the packages they link to, read from install receipts.
+pub async fn get_aliases(app_dir: &AppDir) -> Result<Vec<(String, InstalledPackage)>> {
+    let receipts = load_receipts(app_dir).await?;
+    let mut aliases = vec![];
+    for (package, r) in receipts {
+        for receipt in r.versions {
+            for alias in receipt.aliases {
+                aliases.push((
+                    alias,
+                    InstalledPackage {
+                        package: package.clone(),
+                        version: receipt.version.clone(),
+                    },
+                ));
+            }
+        }
+    }
+    Ok(aliases)
+}
+
+/// receipts of packages in the configuration, receipts are migrated from links at the first time.
+async fn load_receipts(app_dir: &AppDir) -> Result<Vec<(Package, PackageReceipts)>> {
+    migrate_receipts(app_dir).await?;
+    let kaleido = kaleido::load_config(app_dir.get_home_dir()).map_err(Error::other)?;
+    let receipts = PackageReceipts::load_all(app_dir).map_err(Error::other)?;
+    Ok(receipts
+        .into_iter()
+        .filter_map(|r| {
+            kaleido
+                .packages
+                .iter()
+                .find(|p| p.name == r.name)
+                .map(|p| (p.clone(), r))
+        })
+        .collect())
+}
+
+/// create receipts for packages installed before receipts were introduced, it's done only once,
+/// and it should be done before any receipt is written. packages which have receipts are not changed.
+pub async fn migrate_receipts(app_dir: &AppDir) -> Result<()> {
+    if is_migrated(app_dir) {
+        return Ok(());
+    }
+    // nothing has been installed, e.g. the first run
+    if is_empty_dir(app_dir.get_bin_dir()) && is_empty_dir(app_dir.get_alias_dir()) {
+        return mark_migrated(app_dir).map_err(Error::other);
+    }
+    let linked = get_linked_packages(app_dir.get_bin_dir(), app_dir).await?;
+    let aliases = get_linked_packages(app_dir.get_alias_dir(), app_dir).await?;
+    debug!("migrate {} installed packages to receipts", linked.len());
+
+    let mut all: HashMap<String, PackageReceipts> = HashMap::new();
+    let migrated = |ip: &InstalledPackage| Receipt {
+        version: ip.version.clone(),
+        bin_name: ip.package.bin_name.clone(),
+        source_url: None,
+        asset_name: None,
+        sha256: None,
+        aliases: vec![],
+        installed_at: unix_now(),
+        build: None,
+    };
+    for (_, ip) in &linked {
+        all.entry(ip.package.name.clone())
+            .or_insert_with(|| PackageReceipts {
+                name: ip.package.name.clone(),
+                ..Default::default()
+            })
+            .record(migrated(ip));
+    }
+    for (file, ip) in &aliases {
+        let alias = match file.file_stem().and_then(|s| s.to_str()) {
+            Some(a) => a.to_string(),
+            None => continue,
+        };
+        let receipts = all
+            .entry(ip.package.name.clone())
+            .or_insert_with(|| PackageReceipts {
+                name: ip.package.name.clone(),
+                ..Default::default()
+            });
+        if !receipts.versions.iter().any(|r| r.version == ip.version) {
+            receipts.insert(migrated(ip));
+        }
+        if let Some(r) = receipts
+            .versions
+            .iter_mut()
+            .find(|r| r.version == ip.version)
+        {
+            r.aliases.push(alias);
+        }
+    }
+
+    for receipts in all.values() {
+        let existing = PackageReceipts::load(app_dir, &receipts.name).map_err(Error::other)?;
+        if existing.versions.is_empty() {
+            receipts.save(app_dir).map_err(Error::other)?;
+        }
+    }
+    mark_migrated(app_dir).map_err(Error::other)
+}
+
+fn is_empty_dir(dir: &Path) -> bool {
+    std::fs::read_dir(dir)
+        .map(|mut entries| entries.next().is_none())
+        .unwrap_or(true)
+}
+
+/// names of version directories of a package, the latest one is the first.
+pub fn installed_versions(app_dir: &AppDir, name: &str) -> Vec<String> {
+    let mut versions: Vec<String> = match std::fs::read_dir(app_dir.get_packages_dir().join(name)) {
+        Ok(entries) => entries
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().is_dir())
+            .filter_map(|e| e.file_name().to_str().map(|n| n.to_string()))
+            // staging and backup directories of installs
+            .filter(|n| !n.starts_with('.'))
+            .collect(),
+        Err(_) => vec![],
+    };
+    // semver versions first, then others by name
+    versions.sort_by_key(|v| {
+        let parsed = semver::Version::parse(v.trim_start_matches('v')).ok();
+        std::cmp::Reverse((parsed, v.clone()))
+    });
+    versions
+}
+
+/// split 'name@version' into the name and the version.
+pub fn parse_package_spec(spec: &str) -> (String, Option<String>) {
+    match spec.split_once('@') {
+        Some((n, v)) => (n.to_string(), Some(v.to_string())),
+        None => (spec.to_string(), None),
+    }
+}
+
+/// packages linked from files in the directory.
+async fn get_linked_packages(
+    link_dir: &Path,
+    app_dir: &AppDir,
+) -> Result<Vec<(PathBuf, InstalledPackage)>> {
     let kaleido = match kaleido::load_config(app_dir.get_home_dir()) {
         Ok(k) => k,
         Err(e) => {
@@ -33,9 +208,9 @@ pub async fn get_installed_packages(app_dir: &AppDir) -> Result<Vec<InstalledPac
         .iter()
         .map(|p| (p.name.clone(), p))
         .collect();
-    let mut packages: Vec<InstalledPackage> = vec![];
+    let mut packages: Vec<(PathBuf, InstalledPackage)> = vec![];
 
-    match read_dir(app_dir.get_bin_dir()).await {
+    match read_dir(link_dir).await {
         Ok(mut entries) => {
             loop {
                 match entries.next_entry().await {
@@ -51,7 +226,7 @@ pub async fn get_installed_packages(app_dir: &AppDir) -> Result<Vec<InstalledPac
                             {
                                 Ok(o) => {
                                     if let Some(ip) = o {
-                                        packages.push(ip);
+                                        packages.push((file_path, ip));
                                     }
                                 }
                                 Err(e) => {
@@ -245,3 +420,111 @@ async fn get_installed_package(
     }
     Ok(None)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_prerelease_version() {
+        let package: Package = toml::from_str(
+            r#"
+            name = "tool"
+            url = "https://example.com/tool"
+            bin_name = "tool"
+            "#,
+        )
+        .unwrap();
+        let installed = |version: &str| InstalledPackage {
+            package: package.clone(),
+            version: version.to_string(),
+        };
+        assert!(installed("1.0.0-rc.1").is_prerelease());
+        assert!(installed("v2.0.0-beta").is_prerelease());
+        // only semver prereleases are marked, a tag name is not guessed
+        assert!(!installed("nightly-2024-01-01").is_prerelease());
+        assert!(!installed("preview").is_prerelease());
+        assert!(!installed("1.0.0").is_prerelease());
+        assert_eq!("1.0.0-rc.1 (prerelease)", installed("1.0.0-rc.1").display_version());
+        assert_eq!("1.0.0", installed("1.0.0").display_version());
+    }
+
+    #[test]
+    fn test_installed_versions() {
+        use std::fs::create_dir_all;
+
+        let dir = tempfile::tempdir().unwrap();
+        let app_dir = AppDir::from_home(dir.path().join("home")).unwrap();
+        for v in ["0.9.0", "v1.0.0", "1.10.0", "nightly", ".1.10.0.staging"] {
+            create_dir_all(app_dir.get_packages_dir().join("tool").join(v)).unwrap();
+        }
+        assert_eq!(
+            vec!["1.10.0", "v1.0.0", "0.9.0", "nightly"],
+            installed_versions(&app_dir, "tool")
+        );
+        assert!(installed_versions(&app_dir, "other").is_empty());
+        assert_eq!(
+            ("eza".to_string(), Some("0.18.0".to_string())),
+            parse_package_spec("eza@0.18.0")
+        );
+        assert_eq!(("eza".to_string(), None), parse_package_spec("eza"));
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[tokio::test]
+    async fn test_migrate_receipts() {
+        use crate::tool::fs::make_link;
+        use std::fs::{create_dir_all, write};
+
+        let dir = tempfile::tempdir().unwrap();
+        let app_dir = AppDir::from_home(dir.path().join("home")).unwrap();
+        write(
+            app_dir.get_home_dir().join(kaleido::KALEIDO_SYS_FILE),
+            r#"
+            bindles = []
+            [[packages]]
+            name = "tool"
+            url = "https://example.com/tool"
+            bin_name = "tool"
+            "#,
+        )
+        .unwrap();
+        let version_dir = app_dir.get_packages_dir().join("tool").join("1.0.0");
+        create_dir_all(&version_dir).unwrap();
+        write(version_dir.join("tool"), "").unwrap();
+        make_link(&app_dir.get_bin_dir().join("tool"), &version_dir.join("tool")).unwrap();
+        make_link(&app_dir.get_alias_dir().join("tl"), &version_dir.join("tool")).unwrap();
+        // a package installed by a newer sys-kaleido before the migration
+        let mut other = PackageReceipts {
+            name: "other".to_string(),
+            ..Default::default()
+        };
+        other.record(Receipt {
+            version: "2.0.0".to_string(),
+            bin_name: "other".to_string(),
+            source_url: None,
+            asset_name: None,
+            sha256: None,
+            aliases: vec![],
+            installed_at: unix_now(),
+            build: None,
+        });
+        other.save(&app_dir).unwrap();
+
+        let installed = get_installed_packages(&app_dir).await.unwrap();
+        assert_eq!(1, installed.len());
+        assert_eq!("1.0.0", installed[0].version);
+        let aliases = get_aliases(&app_dir).await.unwrap();
+        assert_eq!(1, aliases.len());
+        assert_eq!("tl", aliases[0].0);
+        assert_eq!("tool", aliases[0].1.package.name);
+
+        // receipts are migrated from links
+        let receipts = PackageReceipts::load(&app_dir, "tool").unwrap();
+        assert_eq!(Some("1.0.0"), receipts.current.as_deref());
+        assert_eq!(vec!["tl"], receipts.versions[0].aliases);
+        // the receipt of another package doesn't stop the migration
+        assert_eq!(2, PackageReceipts::load_all(&app_dir).unwrap().len());
+        assert!(is_migrated(&app_dir));
+    }
+}
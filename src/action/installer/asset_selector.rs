@@ -0,0 +1,548 @@
+use super::install::{target_triple, AppAsset, InstallError, Result};
+use crate::tool::kaleido::AssetPattern;
+use log::debug;
+use regex::Regex;
+use std::env::consts::{ARCH, OS};
+
+/// files which are never the package itself.
+const IGNORED_SUFFIXES: [&str; 17] = [
+    ".sha256",
+    ".sha256sum",
+    ".sha512",
+    ".sha512sum",
+    ".md5",
+    ".sig",
+    ".asc",
+    ".pem",
+    ".sbom",
+    ".json",
+    ".txt",
+    ".deb",
+    ".rpm",
+    ".apk",
+    ".msi",
+    ".dmg",
+    ".pkg",
+];
+/// archives which cannot be decompressed.
+const UNSUPPORTED_SUFFIXES: [&str; 5] = [".tar.xz", ".txz", ".tar.bz2", ".tbz2", ".tar.zst"];
+const CHECKSUM_SUFFIXES: [&str; 4] = [".sha256", ".sha256sum", ".sha512", ".sha512sum"];
+
+const OS_SYNONYMS: [(&str, &[&str]); 5] = [
+    ("linux", &["linux"]),
+    ("macos", &["macos", "darwin", "apple", "osx", "mac"]),
+    ("windows", &["windows", "win", "win64", "win32"]),
+    ("freebsd", &["freebsd"]),
+    ("android", &["android"]),
+];
+const ARCH_SYNONYMS: [(&str, &[&str]); 5] = [
+    ("x86_64", &["x86_64", "amd64", "x64"]),
+    ("aarch64", &["aarch64", "arm64", "armv8"]),
+    ("x86", &["x86", "i386", "i586", "i686", "386"]),
+    ("arm", &["arm", "armv6", "armv7", "armhf", "armv7l"]),
+    ("riscv64", &["riscv64", "riscv64gc"]),
+];
+const ABIS: [&str; 4] = ["gnu", "musl", "msvc", "gnueabihf"];
+
+/// the platform assets are selected for.
+pub struct Platform<'a> {
+    pub os: &'a str,
+    pub arch: &'a str,
+    pub abi: &'a str,
+}
+
+impl<'a> Platform<'a> {
+    pub fn current(abi: &'a str) -> Self {
+        Platform {
+            os: OS,
+            arch: ARCH,
+            abi,
+        }
+    }
+}
+
+/// keep assets for the platform, the best one is the first.
+/// if a pattern is given, only matched assets are kept, and they are kept even if nothing tells the platform.
+pub fn select_assets(
+    assets: Vec<AppAsset>,
+    platform: &Platform,
+    remote_file_prefix: &Option<String>,
+    pattern: Option<&AssetPattern>,
+) -> Result<Vec<AppAsset>> {
+    let assets = match pattern {
+        Some(p) => {
+            let re = pattern_regex(p, platform)?;
+            let matched: Vec<AppAsset> = assets
+                .into_iter()
+                .filter(|a| re.is_match(&a.name) && !is_ignored(&a.name.to_lowercase()))
+                .collect();
+            debug!("{} assets match pattern {}", matched.len(), re);
+            let ranked = rank(matched.clone(), platform, remote_file_prefix);
+            if ranked.is_empty() {
+                matched
+            } else {
+                ranked
+            }
+        }
+        None => rank(assets, platform, remote_file_prefix),
+    };
+    Ok(assets)
+}
+
+fn rank(
+    assets: Vec<AppAsset>,
+    platform: &Platform,
+    remote_file_prefix: &Option<String>,
+) -> Vec<AppAsset> {
+    let names: Vec<String> = assets.iter().map(|a| a.name.to_lowercase()).collect();
+    let mut scored: Vec<(i32, AppAsset)> = assets
+        .into_iter()
+        .filter_map(|a| {
+            let s = score(&a.name, &names, platform, remote_file_prefix)?;
+            Some((s, a))
+        })
+        .collect();
+    // the sort is stable, so the order of the release decides between equal scores
+    scored.sort_by_key(|(s, _)| std::cmp::Reverse(*s));
+    if let Some((s, a)) = scored.first() {
+        debug!("select asset {} with score {}", a.name, s);
+    }
+    scored.into_iter().map(|(_, a)| a).collect()
+}
+
+/// None if the asset is not for the platform.
+fn score(
+    name: &str,
+    all_names: &[String],
+    platform: &Platform,
+    remote_file_prefix: &Option<String>,
+) -> Option<i32> {
+    let lower = name.to_lowercase();
+    if is_ignored(&lower) {
+        debug!("skip asset {}: not a package", name);
+        return None;
+    }
+    // keep `x86_64` as one token, it's split by `_` otherwise
+    let normalized = lower.replace("x86_64", "amd64").replace("x86-64", "amd64");
+    let tokens: Vec<&str> = normalized.split(['-', '_', '.']).collect();
+    if let Some(prefix) = remote_file_prefix {
+        if !tokens.contains(&prefix.to_lowercase().as_str()) {
+            debug!("skip asset {}: no prefix {}", name, prefix);
+            return None;
+        }
+    }
+
+    let mut score = 0;
+    match match_synonyms(&tokens, &OS_SYNONYMS, platform.os) {
+        Some(true) => score += 30,
+        Some(false) => {
+            debug!("skip asset {}: for other os", name);
+            return None;
+        }
+        None => {
+            debug!("skip asset {}: no os", name);
+            return None;
+        }
+    }
+    match match_synonyms(&tokens, &ARCH_SYNONYMS, platform.arch) {
+        Some(true) => score += 30,
+        Some(false) => {
+            debug!("skip asset {}: for other arch", name);
+            return None;
+        }
+        None if platform.os == "macos" && tokens.iter().any(|t| t.starts_with("universal")) => {
+            score += 20
+        }
+        None => {
+            debug!("skip asset {}: no arch", name);
+            return None;
+        }
+    }
+    match tokens.iter().find(|t| ABIS.contains(t)) {
+        Some(t) if *t == platform.abi => score += 20,
+        // a static musl binary runs everywhere, but not the other way round
+        Some(&"musl") => score += 5,
+        Some(_) if platform.abi == "musl" => {
+            debug!("skip asset {}: not for musl", name);
+            return None;
+        }
+        Some(_) => score -= 10,
+        None => {}
+    }
+    if lower.contains(&target_triple(platform.os, platform.arch, platform.abi)) {
+        score += 100;
+    }
+    if [".tar.gz", ".tgz", ".zip"]
+        .iter()
+        .any(|s| lower.ends_with(s))
+    {
+        score += 5;
+    }
+    if CHECKSUM_SUFFIXES
+        .iter()
+        .any(|s| all_names.contains(&format!("{}{}", lower, s)))
+    {
+        score += 5;
+    }
+    debug!("asset {} has score {}", name, score);
+    Some(score)
+}
+
+fn is_ignored(lower_name: &str) -> bool {
+    IGNORED_SUFFIXES
+        .iter()
+        .chain(UNSUPPORTED_SUFFIXES.iter())
+        .any(|s| lower_name.ends_with(s))
+}
+
+/// compile the pattern, placeholders become alternations of synonyms.
+fn pattern_regex(pattern: &AssetPattern, platform: &Platform) -> Result<Regex> {
+    let alternation = |table: &[(&str, &[&str])], value: &str| -> String {
+        let synonyms = table
+            .iter()
+            .find(|(k, _)| *k == value)
+            .map(|(_, s)| s.to_vec())
+            .unwrap_or_else(|| vec![value]);
+        let escaped: Vec<String> = synonyms.iter().map(|s| regex::escape(s)).collect();
+        format!("(?:{})", escaped.join("|"))
+    };
+    let placeholders = [
+        ("{os}", alternation(&OS_SYNONYMS, platform.os)),
+        ("{arch}", alternation(&ARCH_SYNONYMS, platform.arch)),
+        ("{abi}", format!("(?:{})", regex::escape(platform.abi))),
+    ];
+    let expression = match pattern {
+        AssetPattern::Regex { regex } => {
+            let mut expression = regex.clone();
+            for (key, value) in &placeholders {
+                expression = expression.replace(key, value);
+            }
+            format!("(?i){}", expression)
+        }
+        AssetPattern::Glob(glob) => {
+            let mut expression = String::from("(?i)^");
+            let mut rest = glob.as_str();
+            while let Some(c) = rest.chars().next() {
+                if let Some((key, value)) = placeholders.iter().find(|(k, _)| rest.starts_with(k)) {
+                    expression.push_str(value);
+                    rest = &rest[key.len()..];
+                    continue;
+                }
+                match c {
+                    '*' => expression.push_str(".*"),
+                    '?' => expression.push('.'),
+                    _ => expression.push_str(&regex::escape(&c.to_string())),
+                }
+                rest = &rest[c.len_utf8()..];
+            }
+            expression.push('$');
+            expression
+        }
+    };
+    Regex::new(&expression)
+        .map_err(|e| InstallError::General(format!("invalid asset pattern: {}", e)))
+}
+
+/// Some(true) if tokens have a synonym of the value, Some(false) if they only have synonyms of others.
+fn match_synonyms(tokens: &[&str], table: &[(&str, &[&str])], value: &str) -> Option<bool> {
+    let mut other = false;
+    for (key, synonyms) in table {
+        if tokens.iter().any(|t| synonyms.contains(t)) {
+            if *key == value {
+                return Some(true);
+            }
+            other = true;
+        }
+    }
+    if other {
+        Some(false)
+    } else if tokens.contains(&value) {
+        Some(true)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn assets(names: &[&str]) -> Vec<AppAsset> {
+        names
+            .iter()
+            .map(|n| AppAsset {
+                name: n.to_string(),
+                download_url: format!("https://example.com/{}", n),
+                bin_path: None,
+            })
+            .collect()
+    }
+
+    const RIPGREP: &[&str] = &[
+        "ripgrep-14.1.0-aarch64-apple-darwin.tar.gz",
+        "ripgrep-14.1.0-aarch64-apple-darwin.tar.gz.sha256",
+        "ripgrep-14.1.0-aarch64-unknown-linux-gnu.tar.gz",
+        "ripgrep-14.1.0-aarch64-unknown-linux-gnu.tar.gz.sha256",
+        "ripgrep-14.1.0-x86_64-apple-darwin.tar.gz",
+        "ripgrep-14.1.0-x86_64-pc-windows-gnu.zip",
+        "ripgrep-14.1.0-x86_64-pc-windows-msvc.zip",
+        "ripgrep-14.1.0-x86_64-pc-windows-msvc.zip.sha256",
+        "ripgrep-14.1.0-x86_64-unknown-linux-musl.tar.gz",
+        "ripgrep-14.1.0-x86_64-unknown-linux-musl.tar.gz.sha256",
+        "ripgrep_14.1.0-1_amd64.deb",
+    ];
+    const GORELEASER: &[&str] = &[
+        "lazygit_0.40.2_Darwin_arm64.tar.gz",
+        "lazygit_0.40.2_Darwin_x86_64.tar.gz",
+        "lazygit_0.40.2_Linux_32-bit.tar.gz",
+        "lazygit_0.40.2_Linux_arm64.tar.gz",
+        "lazygit_0.40.2_Linux_x86_64.tar.gz",
+        "lazygit_0.40.2_Windows_x86_64.zip",
+        "checksums.txt",
+    ];
+    const MIXED: &[&str] = &[
+        "tool-v1.0.0-linux-amd64.tgz",
+        "tool-v1.0.0-linux-amd64.tgz.sig",
+        "tool-v1.0.0-x86_64-unknown-linux-gnu.tar.gz",
+        "tool-v1.0.0-x86_64-unknown-linux-gnu.tar.xz",
+        "tool-v1.0.0-macos-universal.zip",
+        "tool-v1.0.0-win-x64.exe",
+    ];
+
+    #[test]
+    fn test_select_assets() {
+        let cases: [(&[&str], Platform, Option<&str>); 12] = [
+            (
+                RIPGREP,
+                Platform {
+                    os: "linux",
+                    arch: "x86_64",
+                    abi: "gnu",
+                },
+                Some("ripgrep-14.1.0-x86_64-unknown-linux-musl.tar.gz"),
+            ),
+            (
+                RIPGREP,
+                Platform {
+                    os: "linux",
+                    arch: "aarch64",
+                    abi: "gnu",
+                },
+                Some("ripgrep-14.1.0-aarch64-unknown-linux-gnu.tar.gz"),
+            ),
+            (
+                RIPGREP,
+                Platform {
+                    os: "macos",
+                    arch: "aarch64",
+                    abi: "gnu",
+                },
+                Some("ripgrep-14.1.0-aarch64-apple-darwin.tar.gz"),
+            ),
+            (
+                RIPGREP,
+                Platform {
+                    os: "windows",
+                    arch: "x86_64",
+                    abi: "msvc",
+                },
+                Some("ripgrep-14.1.0-x86_64-pc-windows-msvc.zip"),
+            ),
+            (
+                RIPGREP,
+                Platform {
+                    os: "windows",
+                    arch: "x86_64",
+                    abi: "gnu",
+                },
+                Some("ripgrep-14.1.0-x86_64-pc-windows-gnu.zip"),
+            ),
+            (
+                RIPGREP,
+                Platform {
+                    os: "linux",
+                    arch: "x86",
+                    abi: "gnu",
+                },
+                None,
+            ),
+            (
+                GORELEASER,
+                Platform {
+                    os: "linux",
+                    arch: "x86_64",
+                    abi: "gnu",
+                },
+                Some("lazygit_0.40.2_Linux_x86_64.tar.gz"),
+            ),
+            (
+                GORELEASER,
+                Platform {
+                    os: "macos",
+                    arch: "aarch64",
+                    abi: "gnu",
+                },
+                Some("lazygit_0.40.2_Darwin_arm64.tar.gz"),
+            ),
+            (
+                GORELEASER,
+                Platform {
+                    os: "windows",
+                    arch: "x86_64",
+                    abi: "msvc",
+                },
+                Some("lazygit_0.40.2_Windows_x86_64.zip"),
+            ),
+            (
+                MIXED,
+                Platform {
+                    os: "linux",
+                    arch: "x86_64",
+                    abi: "gnu",
+                },
+                Some("tool-v1.0.0-x86_64-unknown-linux-gnu.tar.gz"),
+            ),
+            (
+                MIXED,
+                Platform {
+                    os: "macos",
+                    arch: "x86_64",
+                    abi: "gnu",
+                },
+                Some("tool-v1.0.0-macos-universal.zip"),
+            ),
+            (
+                MIXED,
+                Platform {
+                    os: "windows",
+                    arch: "x86_64",
+                    abi: "msvc",
+                },
+                Some("tool-v1.0.0-win-x64.exe"),
+            ),
+        ];
+        for (names, platform, expected) in cases {
+            let selected = select_assets(assets(names), &platform, &None, None).unwrap();
+            assert_eq!(
+                expected,
+                selected.first().map(|a| a.name.as_str()),
+                "{} {} {}",
+                platform.os,
+                platform.arch,
+                platform.abi
+            );
+        }
+    }
+
+    #[test]
+    fn test_musl_only() {
+        let platform = Platform {
+            os: "linux",
+            arch: "x86_64",
+            abi: "musl",
+        };
+        let selected = select_assets(
+            assets(&[
+                "tool-x86_64-unknown-linux-gnu.tar.gz",
+                "tool-x86_64-unknown-linux-musl.tar.gz",
+            ]),
+            &platform,
+            &None,
+            None,
+        )
+        .unwrap();
+        let names: Vec<_> = selected.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(vec!["tool-x86_64-unknown-linux-musl.tar.gz"], names);
+    }
+
+    #[test]
+    fn test_checksum_and_prefix() {
+        let platform = Platform {
+            os: "linux",
+            arch: "x86_64",
+            abi: "gnu",
+        };
+        let selected = select_assets(
+            assets(&[
+                "cli-linux-amd64.tar.gz",
+                "server-linux-amd64.tar.gz",
+                "server-linux-amd64.tar.gz.sha256sum",
+            ]),
+            &platform,
+            &None,
+            None,
+        )
+        .unwrap();
+        assert_eq!("server-linux-amd64.tar.gz", selected[0].name);
+        assert_eq!(2, selected.len());
+
+        let selected = select_assets(
+            assets(&["cli-linux-amd64.tar.gz", "server-linux-amd64.tar.gz"]),
+            &platform,
+            &Some("cli".to_string()),
+            None,
+        )
+        .unwrap();
+        assert_eq!(1, selected.len());
+        assert_eq!("cli-linux-amd64.tar.gz", selected[0].name);
+    }
+
+    const FUC: &[&str] = &[
+        "cpz-aarch64-apple-darwin",
+        "cpz-x86_64-pc-windows-msvc.exe",
+        "cpz-x86_64-unknown-linux-gnu",
+        "rmz-aarch64-apple-darwin",
+        "rmz-x86_64-pc-windows-msvc.exe",
+        "rmz-x86_64-unknown-linux-gnu",
+    ];
+
+    #[test]
+    fn test_asset_pattern() {
+        let platform = Platform {
+            os: "linux",
+            arch: "x86_64",
+            abi: "gnu",
+        };
+        let cases = [
+            (
+                AssetPattern::Glob("rmz-{arch}-*".to_string()),
+                Some("rmz-x86_64-unknown-linux-gnu"),
+            ),
+            (
+                AssetPattern::Glob("RMZ-*-{os}-{abi}".to_string()),
+                Some("rmz-x86_64-unknown-linux-gnu"),
+            ),
+            (
+                AssetPattern::Regex {
+                    regex: "^cpz-{arch}-.*$".to_string(),
+                },
+                Some("cpz-x86_64-unknown-linux-gnu"),
+            ),
+            (AssetPattern::Glob("cpz-*-freebsd".to_string()), None),
+        ];
+        for (pattern, expected) in cases {
+            let selected = select_assets(assets(FUC), &platform, &None, Some(&pattern)).unwrap();
+            assert_eq!(
+                expected,
+                selected.first().map(|a| a.name.as_str()),
+                "{:?}",
+                pattern
+            );
+        }
+
+        // the pattern is trusted even if the name has no platform in it
+        let selected = select_assets(
+            assets(&["tool.tar.gz", "tool.tar.gz.sha256"]),
+            &platform,
+            &None,
+            Some(&AssetPattern::Glob("tool.*".to_string())),
+        )
+        .unwrap();
+        assert_eq!(1, selected.len());
+
+        let invalid = AssetPattern::Regex {
+            regex: "(".to_string(),
+        };
+        assert!(select_assets(assets(FUC), &platform, &None, Some(&invalid)).is_err());
+    }
+}
@@ -0,0 +1,515 @@
+use super::install::{rust_target, AppAsset, InstallError, Result};
+use super::provider::RemoteRelease;
+use crate::tool::{
+    cache::download_cached,
+    fs::{read_from_archive, AppDir},
+    kaleido::{Binstall, Package},
+};
+use indicatif::ProgressBar;
+use log::debug;
+use regex::{Captures, Regex};
+use std::collections::HashMap;
+use std::env::consts::{ARCH, EXE_SUFFIX};
+use std::fs;
+use std::path::{Component, Path};
+use std::sync::{Mutex, OnceLock};
+
+const DEFAULT_PKG_URL: &str =
+    "{ repo }/releases/download/v{ version }/{ name }-{ target }-v{ version }{ archive-suffix }";
+const DEFAULT_BIN_DIR: &str = "{ name }-{ target }-v{ version }/{ bin }{ binary-ext }";
+const DEFAULT_PKG_FMT: &str = "tgz";
+
+/// binstall metadata read from source archives by their urls, so an archive is read once in a command.
+#[derive(Default)]
+pub struct SourceMetadata {
+    loaded: Mutex<HashMap<String, Option<Binstall>>>,
+}
+
+/// how source archives are downloaded to read their binstall metadata.
+pub struct SourceLoader<'a> {
+    pub app_dir: &'a AppDir,
+    /// the temporary directory of the install
+    pub tmp_dir: &'a Path,
+    pub metadata: &'a SourceMetadata,
+    pub pb: &'a ProgressBar,
+}
+
+/// templates for one target, after all sources are merged.
+#[derive(Clone, Debug, Default)]
+pub struct Templates {
+    pkg_url: Option<String>,
+    bin_dir: Option<String>,
+    pkg_fmt: Option<String>,
+}
+
+impl Templates {
+    fn new(binstall: &Binstall, target: &str) -> Self {
+        let overrides = binstall.overrides.get(target);
+        Templates {
+            pkg_url: overrides
+                .and_then(|o| o.pkg_url.clone())
+                .or_else(|| binstall.pkg_url.clone()),
+            bin_dir: overrides
+                .and_then(|o| o.bin_dir.clone())
+                .or_else(|| binstall.bin_dir.clone()),
+            pkg_fmt: overrides
+                .and_then(|o| o.pkg_fmt.clone())
+                .or_else(|| binstall.pkg_fmt.clone()),
+        }
+    }
+
+    fn or(self, other: Templates) -> Self {
+        Templates {
+            pkg_url: self.pkg_url.or(other.pkg_url),
+            bin_dir: self.bin_dir.or(other.bin_dir),
+            pkg_fmt: self.pkg_fmt.or(other.pkg_fmt),
+        }
+    }
+}
+
+/// templates of current target, `from_source` metadata is read from the source archive of the release.
+pub async fn binstall_templates(
+    binstall: &Binstall,
+    package: &Package,
+    release: &RemoteRelease,
+    rust_abi: &str,
+    loader: &SourceLoader<'_>,
+) -> Result<Templates> {
+    let target = rust_target(rust_abi);
+    let mut templates = Templates::new(binstall, &target);
+    if binstall.from_source {
+        if let Some(source) = load_from_source(package, release, loader).await? {
+            templates = templates.or(Templates::new(&source, &target));
+        }
+    }
+    debug!("binstall templates for {}: {:?}", target, templates);
+    Ok(templates)
+}
+
+/// find the asset described by binstall templates, the returned asset also has the path of the executable file.
+/// if `pkg-url` is specified but the file is not in the release, it's downloaded from the url directly.
+pub fn select_binstall_asset(
+    templates: &Templates,
+    package: &Package,
+    version: &str,
+    assets: &[AppAsset],
+    rust_abi: &str,
+) -> Option<AppAsset> {
+    select(
+        templates.clone(),
+        package,
+        version,
+        assets,
+        &rust_target(rust_abi),
+        rust_abi,
+    )
+}
+
+fn select(
+    templates: Templates,
+    package: &Package,
+    version: &str,
+    assets: &[AppAsset],
+    target: &str,
+    rust_abi: &str,
+) -> Option<AppAsset> {
+    let pkg_fmt = templates.pkg_fmt.as_deref().unwrap_or(DEFAULT_PKG_FMT);
+    let suffixes = archive_suffixes(pkg_fmt);
+    let pkg_url = templates.pkg_url.as_deref().unwrap_or(DEFAULT_PKG_URL);
+    let bin_dir = templates.bin_dir.as_deref().unwrap_or(DEFAULT_BIN_DIR);
+    let name = match package
+        .crates_io
+        .as_ref()
+        .and_then(|c| c.crate_name.as_ref())
+    {
+        Some(n) => n.as_str(),
+        None => package.name.as_str(),
+    };
+
+    let mut candidates = vec![];
+    for suffix in suffixes {
+        let values = |key: &str| -> Option<String> {
+            let v = match key {
+                "name" => name,
+                "version" => version,
+                "target" => target,
+                "archive-format" => suffix.trim_start_matches('.'),
+                "archive-suffix" => suffix,
+                "binary-ext" => EXE_SUFFIX,
+                "bin" => package.bin_name.as_str(),
+                "repo" => package.url.trim_end_matches('/'),
+                "target-arch" => ARCH,
+                "target-libc" => rust_abi,
+                "target-family" => {
+                    if cfg!(target_os = "windows") {
+                        "windows"
+                    } else {
+                        "unix"
+                    }
+                }
+                _ => return None,
+            };
+            Some(v.to_string())
+        };
+        let url = render(pkg_url, values);
+        // a raw binary has no directory
+        let bin_path = if pkg_fmt == "bin" {
+            None
+        } else {
+            Some(render(bin_dir, values))
+        };
+        candidates.push((url, bin_path));
+    }
+
+    for (url, bin_path) in &candidates {
+        let file_name = url.rsplit('/').next().unwrap_or_default();
+        if let Some(asset) = assets
+            .iter()
+            .find(|a| &a.download_url == url || a.name == file_name)
+        {
+            debug!("binstall asset: {}, bin path: {:?}", asset.name, bin_path);
+            return Some(AppAsset {
+                bin_path: bin_path.clone(),
+                ..asset.clone()
+            });
+        }
+    }
+
+    if templates.pkg_url.is_some() {
+        if let Some((url, bin_path)) = candidates.into_iter().next() {
+            debug!(
+                "binstall asset is not in the release, download from {}",
+                url
+            );
+            return Some(AppAsset {
+                name: url.rsplit('/').next().unwrap_or_default().to_string(),
+                download_url: url,
+                bin_path,
+            });
+        }
+    }
+    None
+}
+
+/// possible file suffixes for a package format, see `pkg-fmt` of cargo-binstall.
+/// formats which cannot be decompressed, e.g. tar, tbz2, txz and tzstd, are not selected.
+fn archive_suffixes(pkg_fmt: &str) -> Vec<&'static str> {
+    match pkg_fmt {
+        "tgz" => vec![".tgz", ".tar.gz"],
+        "zip" => vec![".zip"],
+        "bin" => {
+            if EXE_SUFFIX.is_empty() {
+                vec!["", ".bin"]
+            } else {
+                vec![EXE_SUFFIX]
+            }
+        }
+        _ => vec![],
+    }
+}
+
+/// replace `{ key }` placeholders, unknown ones are kept as they are.
+fn render<F>(template: &str, values: F) -> String
+where
+    F: Fn(&str) -> Option<String>,
+{
+    static PLACEHOLDER: OnceLock<Regex> = OnceLock::new();
+    let re = PLACEHOLDER.get_or_init(|| {
+        Regex::new(r"\{\s*([\w-]+)\s*\}").expect("invalid binstall placeholder regex")
+    });
+    re.replace_all(template, |caps: &Captures| {
+        values(&caps[1]).unwrap_or_else(|| caps[0].to_string())
+    })
+    .into_owned()
+}
+
+/// read `[package.metadata.binstall]` from Cargo.toml in the source archive,
+/// the archive is downloaded into the temporary directory through the download cache.
+async fn load_from_source(
+    package: &Package,
+    release: &RemoteRelease,
+    loader: &SourceLoader<'_>,
+) -> Result<Option<Binstall>> {
+    let source = match release.source.as_ref() {
+        Some(s) => s,
+        None => {
+            debug!("no source archive to read binstall metadata");
+            return Ok(None);
+        }
+    };
+    if let Some(binstall) = loader.metadata.loaded.lock().unwrap().get(&source.url) {
+        return Ok(binstall.clone());
+    }
+    let file = loader.tmp_dir.join(&source.file_name);
+    let identity = format!(
+        "{}@{}",
+        release.tag_name,
+        release.published_at.as_deref().unwrap_or_default()
+    );
+    download_cached(
+        loader.app_dir,
+        &file,
+        &source.url,
+        Some(&identity),
+        loader.pb,
+    )
+    .await?;
+    let binstall = read_source_metadata(package, &source.file_name, &fs::read(&file)?)?;
+    loader
+        .metadata
+        .loaded
+        .lock()
+        .unwrap()
+        .insert(source.url.clone(), binstall.clone());
+    Ok(binstall)
+}
+
+fn read_source_metadata(
+    package: &Package,
+    file_name: &str,
+    content: &[u8],
+) -> Result<Option<Binstall>> {
+    // the crate may be a member of a workspace
+    let candidates = [
+        vec!["Cargo.toml"],
+        vec![package.name.as_str(), "Cargo.toml"],
+        vec!["crates", package.name.as_str(), "Cargo.toml"],
+    ];
+    for candidate in candidates {
+        let manifest = read_from_archive(file_name, content, |p| is_manifest(p, &candidate))?;
+        if let Some(m) = manifest {
+            let value: toml::Value = toml::from_str(&m)
+                .map_err(|e| InstallError::General(format!("invalid Cargo.toml: {}", e)))?;
+            let metadata = value
+                .get("package")
+                .and_then(|p| p.get("metadata"))
+                .and_then(|m| m.get("binstall"));
+            if let Some(metadata) = metadata {
+                let binstall: Binstall = metadata.clone().try_into().map_err(|e| {
+                    InstallError::General(format!("invalid binstall metadata: {}", e))
+                })?;
+                return Ok(Some(binstall));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// archives have a top folder, so the path is checked without its first component.
+fn is_manifest(path: &Path, expected: &[&str]) -> bool {
+    let components: Vec<_> = path
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(n) => n.to_str(),
+            _ => None,
+        })
+        .collect();
+    components.len() == expected.len() + 1 && components[1..] == *expected
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::action::installer::install::AppSource;
+    use flate2::{write::GzEncoder, Compression};
+    use mockito::Server;
+
+    const TARGET: &str = "x86_64-unknown-linux-gnu";
+
+    fn package() -> Package {
+        toml::from_str(
+            r#"
+            name = "tool"
+            url = "https://github.com/org/tool"
+            language = "rust"
+            bin_name = "tl"
+            "#,
+        )
+        .unwrap()
+    }
+
+    fn release() -> RemoteRelease {
+        RemoteRelease {
+            tag_name: "v1.2.0".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn asset(name: &str) -> AppAsset {
+        AppAsset {
+            name: name.to_string(),
+            download_url: format!(
+                "https://github.com/org/tool/releases/download/v1.2.0/{}",
+                name
+            ),
+            bin_path: None,
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let values = |k: &str| match k {
+            "name" => Some("tool".to_string()),
+            "version" => Some("1.0.0".to_string()),
+            _ => None,
+        };
+        assert_eq!("tool-1.0.0", render("{ name }-{version}", values));
+        assert_eq!("tool-{ unknown }", render("{ name }-{ unknown }", values));
+    }
+
+    #[test]
+    fn test_default_templates() {
+        let assets = vec![
+            asset("tool-x86_64-apple-darwin-v1.2.0.tgz"),
+            asset("tool-x86_64-unknown-linux-gnu-v1.2.0.tar.gz"),
+        ];
+        let selected = select(
+            Templates::default(),
+            &package(),
+            "1.2.0",
+            &assets,
+            TARGET,
+            "gnu",
+        )
+        .unwrap();
+        assert_eq!("tool-x86_64-unknown-linux-gnu-v1.2.0.tar.gz", selected.name);
+        assert_eq!(
+            Some(format!(
+                "tool-x86_64-unknown-linux-gnu-v1.2.0/tl{}",
+                EXE_SUFFIX
+            )),
+            selected.bin_path
+        );
+
+        let missing = select(
+            Templates::default(),
+            &package(),
+            "1.2.0",
+            &assets[0..1],
+            TARGET,
+            "gnu",
+        );
+        assert!(missing.is_none());
+    }
+
+    #[test]
+    fn test_custom_templates() {
+        let binstall: Binstall = toml::from_str(
+            r#"
+            pkg-url = "https://cdn.example.com/{ version }/{ target }.zip"
+            pkg-fmt = "zip"
+            bin-dir = "bin/{ bin }{ binary-ext }"
+            [overrides.x86_64-unknown-linux-gnu]
+            pkg-fmt = "bin"
+            pkg-url = "https://cdn.example.com/{ version }/{ bin }-{ target-arch }"
+            "#,
+        )
+        .unwrap();
+
+        let other = select(
+            Templates::new(&binstall, "aarch64-apple-darwin"),
+            &package(),
+            "1.2.0",
+            &[],
+            "aarch64-apple-darwin",
+            "gnu",
+        )
+        .unwrap();
+        assert_eq!(
+            "https://cdn.example.com/1.2.0/aarch64-apple-darwin.zip",
+            other.download_url
+        );
+        assert_eq!(Some(format!("bin/tl{}", EXE_SUFFIX)), other.bin_path);
+
+        let linux = select(
+            Templates::new(&binstall, TARGET),
+            &package(),
+            "1.2.0",
+            &[],
+            TARGET,
+            "gnu",
+        )
+        .unwrap();
+        assert_eq!(
+            format!("https://cdn.example.com/1.2.0/tl-{}", ARCH),
+            linux.download_url
+        );
+        assert!(linux.bin_path.is_none());
+    }
+
+    fn source_archive() -> Vec<u8> {
+        let manifest = br#"
+[package]
+name = "tool"
+version = "1.2.0"
+
+[package.metadata.binstall]
+pkg-url = "{ repo }/releases/download/{ version }/tool-{ target }.zip"
+pkg-fmt = "zip"
+bin-dir = "{ bin }{ binary-ext }"
+"#;
+        let mut builder = tar::Builder::new(GzEncoder::new(vec![], Compression::default()));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(manifest.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "tool-1.2.0/Cargo.toml", &manifest[..])
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_from_source() {
+        let mut server = Server::new_async().await;
+        let mock = server
+            .mock("GET", "/tool-1.2.0.crate")
+            .with_body(source_archive())
+            .expect(1)
+            .create_async()
+            .await;
+        let mut release = release();
+        release.source = Some(AppSource {
+            url: format!("{}/tool-1.2.0.crate", server.url()),
+            file_name: "tool-1.2.0.crate".to_string(),
+        });
+        let target = rust_target("gnu");
+        let assets = vec![asset(&format!("tool-{}.zip", target))];
+        let binstall = Binstall {
+            from_source: true,
+            ..Default::default()
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let app_dir = AppDir::from_home(dir.path().join("home")).unwrap();
+        let metadata = SourceMetadata::default();
+        let pb = ProgressBar::hidden();
+        let loader = SourceLoader {
+            app_dir: &app_dir,
+            tmp_dir: dir.path(),
+            metadata: &metadata,
+            pb: &pb,
+        };
+
+        let templates = binstall_templates(&binstall, &package(), &release, "gnu", &loader)
+            .await
+            .unwrap();
+        let selected =
+            select_binstall_asset(&templates, &package(), "1.2.0", &assets, "gnu").unwrap();
+        assert_eq!(format!("tool-{}.zip", target), selected.name);
+        assert_eq!(Some(format!("tl{}", EXE_SUFFIX)), selected.bin_path);
+
+        // the archive is downloaded once
+        let templates = binstall_templates(&binstall, &package(), &release, "gnu", &loader)
+            .await
+            .unwrap();
+        let again = select_binstall_asset(&templates, &package(), "1.2.0", &assets, "gnu").unwrap();
+        assert_eq!(selected.name, again.name);
+        mock.assert_async().await;
+    }
+
+    #[test]
+    fn test_unsupported_formats() {
+        assert!(archive_suffixes("txz").is_empty());
+        assert_eq!(vec![".tgz", ".tar.gz"], archive_suffixes("tgz"));
+    }
+}
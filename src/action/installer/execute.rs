@@ -1,10 +1,17 @@
 use super::{
-    install::{Installer, InstallerContext},
-    rust_bin_installer::RustBinInstaller,
+    install::{platform_keys, InstallerContext},
+    provider::PackageProvider,
+    rust_bin_installer::{prepare, Prepared, RustBinInstaller},
 };
-use crate::tool::{fs::AppDir, global_input::GlobalInput, kaleido};
+use crate::tool::{
+    fs::AppDir,
+    global_input::GlobalInput,
+    http::download_bar,
+    kaleido::{self, Kaleido, Package},
+};
+use futures_util::{stream, StreamExt};
+use indicatif::{MultiProgress, ProgressBar};
 use log::{error, warn};
-use std::fs::{create_dir, remove_dir_all};
 
 pub const LATEST_VERSION: &str = "LATEST";
 
@@ -13,73 +20,243 @@ pub struct InstallRequest {
     pub name: String,
     pub alias: Option<String>,
     pub version: Option<String>,
+    /// include prereleases when resolving the version.
+    pub prerelease: bool,
+}
+
+/// result of installing a package.
+#[derive(Clone, Debug, PartialEq)]
+pub enum InstallStatus {
+    /// the version is installed and linked.
+    Installed(String),
+    /// the version has been installed before.
+    Unchanged(String),
+    Failed,
 }
 
+/// install packages, releases are found and downloaded by `context.jobs` packages at the same time,
+/// then packages are linked one by one, statuses are in the same order as requests.
 pub async fn install<'a, 'b>(
     packages: Vec<InstallRequest>,
     app_dir: &AppDir,
     global_input: &'b mut GlobalInput<'a>,
     context: &InstallerContext,
-) where
-    'a: 'b,
-{
-    for request in packages {
-        install_one(global_input, &request, app_dir, context).await;
-    }
-}
-
-pub async fn install_one<'a, 'b>(
-    global_input: &'b mut GlobalInput<'a>,
-    request: &InstallRequest,
-    app_dir: &AppDir,
-    context: &InstallerContext,
-) where
+) -> Vec<InstallStatus>
+where
     'a: 'b,
 {
-    println!(
-        "=================install package {}=================",
-        request.name
-    );
-    let home_dir = app_dir.get_home_dir();
-    let kaleido = match kaleido::load_config(home_dir) {
+    let kaleido = match kaleido::load_config(app_dir.get_home_dir()) {
         Ok(k) => k,
         Err(e) => {
             error!("Failed to load metadata: {}", e);
-            return;
+            return vec![InstallStatus::Failed; packages.len()];
         }
     };
 
+    let multi = MultiProgress::new();
+    let kaleido = &kaleido;
+    let multi = &multi;
+    let prepared: Vec<_> = stream::iter(packages)
+        .map(|request| async move {
+            let pb = multi.add(download_bar(&request.name));
+            let prepared = prepare_one(kaleido, &request, app_dir, context, &pb).await;
+            (request, prepared)
+        })
+        .buffered(context.jobs.max(1))
+        .collect()
+        .await;
+
+    let mut statuses = vec![];
+    for (request, prepared) in prepared {
+        let status = match prepared {
+            Some((package, prepared)) => {
+                RustBinInstaller::new(global_input)
+                    .finish(prepared, &package, &request, app_dir)
+                    .await
+            }
+            None => InstallStatus::Failed,
+        };
+        statuses.push(status);
+    }
+    statuses
+}
+
+/// the package and the first step of its install, which doesn't change any link.
+async fn prepare_one(
+    kaleido: &Kaleido,
+    request: &InstallRequest,
+    app_dir: &AppDir,
+    context: &InstallerContext,
+    pb: &ProgressBar,
+) -> Option<(Package, Prepared)> {
     let package = match kaleido.packages.iter().find(|p| p.name == request.name) {
-        Some(p) => p,
+        Some(p) => p.for_platform(&platform_keys(&context.rust_abi)),
         None => {
             error!("cannot find package with name {}", request.name);
-            return;
+            pb.abandon_with_message("cannot find the package");
+            return None;
         }
     };
 
-    let tmp_dir = home_dir.join("tmp");
-    if tmp_dir.exists() && tmp_dir.is_dir() {
-        if let Err(e) = remove_dir_all(&tmp_dir) {
-            error!("failed to remove dir: {}", e);
-            return;
+    let provider = match PackageProvider::from_package(&package, &context.rust_abi) {
+        Ok(p) => p,
+        Err(e) => {
+            error!("cannot install package {}: {}", request.name, e);
+            pb.abandon_with_message(format!("cannot install the package: {}", e));
+            return None;
         }
-    }
-    if let Err(e) = create_dir(&tmp_dir) {
-        error!("failed to create dir: {}", e);
-        return;
-    }
+    };
 
     match &package.language {
         Some(lang) => match lang.to_lowercase().as_str() {
             "rust" => {
-                RustBinInstaller::new(global_input)
-                    .install(package, request, app_dir, context)
-                    .await;
+                let prepared = prepare(&provider, &package, request, app_dir, context, pb).await;
+                Some((package, prepared))
             }
             _ => {
                 warn!("unsupported language: {}", lang);
+                pb.abandon_with_message(format!("unsupported language: {}", lang));
+                None
             }
         },
-        None => {}
+        None => {
+            warn!("no language is specified for package {}", package.name);
+            pb.abandon_with_message("no language is specified");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::action::installer::test::archive;
+    use crate::tool::kaleido::KALEIDO_SYS_FILE;
+    use mockito::{Mock, Server, ServerGuard};
+    use std::env::consts::{EXE_EXTENSION, EXE_SUFFIX};
+    use std::fs::{read_link, write};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    /// the response is delayed, and the most downloads at the same time are counted.
+    async fn mock_download(
+        server: &mut ServerGuard,
+        name: &str,
+        delay: u64,
+        running: Arc<AtomicUsize>,
+        max_running: Arc<AtomicUsize>,
+    ) -> Mock {
+        let body = archive(&[
+            &format!("{}-1.0.0/", name),
+            &format!("{}-1.0.0/{}{}", name, name, EXE_SUFFIX),
+        ]);
+        server
+            .mock("GET", format!("/{}-1.0.0.tar.gz", name).as_str())
+            .with_chunked_body(move |w| {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                max_running.fetch_max(now, Ordering::SeqCst);
+                std::thread::sleep(Duration::from_millis(delay));
+                running.fetch_sub(1, Ordering::SeqCst);
+                w.write_all(&body)
+            })
+            .create_async()
+            .await
+    }
+
+    fn app_dir(server_url: &str) -> (TempDir, AppDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let app_dir = AppDir::from_home(dir.path().join("home")).unwrap();
+        let mut config = "bindles = []\n".to_string();
+        for name in ["alpha", "beta"] {
+            config.push_str(&format!(
+                r#"
+                [[packages]]
+                name = "{name}"
+                url = "https://example.com/{name}"
+                language = "rust"
+                bin_name = "{name}"
+                [packages.url_template]
+                url = "{server_url}/{name}-{{version}}.{{ext}}"
+                windows_ext = "tar.gz"
+                versions = ["1.0.0"]
+                "#
+            ));
+        }
+        write(app_dir.get_home_dir().join(KALEIDO_SYS_FILE), config).unwrap();
+        (dir, app_dir)
+    }
+
+    fn request(name: &str) -> InstallRequest {
+        InstallRequest {
+            name: name.to_string(),
+            alias: Some("t".to_string()),
+            version: None,
+            prerelease: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_install_packages() {
+        for jobs in [1, 2] {
+            let mut server = Server::new_async().await;
+            let running = Arc::new(AtomicUsize::new(0));
+            let max_running = Arc::new(AtomicUsize::new(0));
+            // beta is downloaded first if they are downloaded at the same time
+            let alpha = mock_download(
+                &mut server,
+                "alpha",
+                300,
+                running.clone(),
+                max_running.clone(),
+            )
+            .await;
+            let beta = mock_download(
+                &mut server,
+                "beta",
+                50,
+                running.clone(),
+                max_running.clone(),
+            )
+            .await;
+            let (_dir, app_dir) = app_dir(&server.url());
+            let mut stdin = std::io::stdin();
+            let mut global_input = GlobalInput::new(&mut stdin);
+            let context = InstallerContext {
+                rust_abi: "gnu".to_string(),
+                force: false,
+                jobs,
+                ..Default::default()
+            };
+
+            let statuses = install(
+                vec![request("alpha"), request("beta"), request("missing")],
+                &app_dir,
+                &mut global_input,
+                &context,
+            )
+            .await;
+
+            assert_eq!(
+                vec![
+                    InstallStatus::Installed("1.0.0".to_string()),
+                    InstallStatus::Installed("1.0.0".to_string()),
+                    InstallStatus::Failed,
+                ],
+                statuses,
+                "jobs: {}",
+                jobs
+            );
+            assert_eq!(jobs, max_running.load(Ordering::SeqCst));
+            // packages are activated in the order of requests, so the last one owns the alias
+            let mut alias = app_dir.get_alias_dir().join("t");
+            alias.set_extension(EXE_EXTENSION);
+            let mut beta_bin = app_dir.get_packages_dir().join("beta/1.0.0/beta");
+            beta_bin.set_extension(EXE_EXTENSION);
+            assert_eq!(beta_bin, read_link(alias).unwrap());
+            alpha.assert_async().await;
+            beta.assert_async().await;
+        }
     }
 }
@@ -1,97 +1,259 @@
-use super::execute::{InstallRequest, LATEST_VERSION};
+use super::asset_selector::{select_assets, Platform};
+use super::binstall::{
+    binstall_templates, select_binstall_asset, SourceLoader, SourceMetadata, Templates,
+};
+use super::execute::LATEST_VERSION;
+use super::provider::{normalize_version, ReleaseProvider, RemoteRelease, TagFormat};
 use crate::tool::{
     fs::{AppDir, FSError},
-    kaleido::{Github, Package},
     http,
+    kaleido::{Channel, Package},
+    state::{PackageReceipts, Receipt},
 };
 use log::{debug, error};
-use octocrab::models::repos::Release;
 use reqwest::{self};
+use semver::{Prerelease, Version, VersionReq};
 use std::env::consts::{ARCH, OS};
-use std::{collections::HashSet, vec};
 use thiserror::Error;
 
-type Result<T> = std::result::Result<T, InstallError>;
+pub type Result<T> = std::result::Result<T, InstallError>;
 
+#[derive(Default)]
 pub struct InstallerContext {
     pub rust_abi: String,
     /// if true, the installed version will be deleted, then install the package again, even it's the same version.
     /// otherwise, the package will be skipped if the given version has been installed.
     pub force: bool,
+    /// how many packages are found and downloaded at the same time.
+    pub jobs: usize,
+    /// binstall metadata of source archives read by installs of the command.
+    pub binstall_sources: SourceMetadata,
 }
 
-pub trait Installer {
-    /// install a package, this method should not panic.
-    /// return true if success, return false otherwise.
-    async fn install(
-        &mut self,
-        package: &Package,
-        request: &InstallRequest,
-        app_dir: &AppDir,
-        context: &InstallerContext,
-    );
-}
-
-pub async fn find_assets(
+pub async fn find_assets<P: ReleaseProvider>(
+    provider: &P,
     package: &Package,
     version: &str,
     remote_file_prefix: &Option<String>,
     rust_abi: &str,
+    prerelease: bool,
+    loader: &SourceLoader<'_>,
 ) -> Result<AppRelease> {
     debug!(
         "try to find assets: {}, {}, {}",
         package.name, version, rust_abi
     );
-    match package.github.as_ref() {
-        Some(gt) => {
-            let release = match find_github_release(gt, version).await {
-                Ok(o) => match o {
-                    Some(r) => r,
-                    None => {
-                        return Err(InstallError::GeneralStr("cannot find appropriate release"))
-                    }
-                },
-                Err(e) => {
-                    return Err(e);
+    let tag_format = TagFormat::from_package(package);
+    let prerelease = prerelease || package.channel == Some(Channel::Prerelease);
+    let release = match find_release(provider, version, &tag_format, prerelease).await? {
+        Some(r) => r,
+        None => return Err(InstallError::GeneralStr("cannot find appropriate release")),
+    };
+
+    // the tag may not match the format if it's not from a forge, e.g. a crate version
+    let normalized_version = tag_format
+        .version_of(&release.tag_name)
+        .unwrap_or_else(|| normalize_version(&release.tag_name).to_string());
+    let assets = release_assets(
+        provider,
+        package,
+        &release,
+        &normalized_version,
+        remote_file_prefix,
+        rust_abi,
+        loader,
+    )
+    .await?;
+
+    Ok(AppRelease {
+        identity: Some(format!(
+            "{}@{}",
+            normalized_version,
+            release.published_at.as_deref().unwrap_or_default()
+        )),
+        version: normalized_version,
+        source: release.source,
+        assets,
+    })
+}
+
+/// assets of the release for current platform, the best one is the first.
+pub async fn release_assets<P: ReleaseProvider>(
+    provider: &P,
+    package: &Package,
+    release: &RemoteRelease,
+    version: &str,
+    remote_file_prefix: &Option<String>,
+    rust_abi: &str,
+    loader: &SourceLoader<'_>,
+) -> Result<Vec<AppAsset>> {
+    let templates = match package.binstall.as_ref() {
+        Some(b) => Some(binstall_templates(b, package, release, rust_abi, loader).await?),
+        None => None,
+    };
+    let assets = provider.assets(release).await?;
+    select_release_assets(
+        provider,
+        package,
+        assets,
+        version,
+        templates.as_ref(),
+        remote_file_prefix,
+        rust_abi,
+    )
+}
+
+/// select assets for current platform from the assets of a release, binstall templates are tried first.
+pub fn select_release_assets<P: ReleaseProvider>(
+    provider: &P,
+    package: &Package,
+    assets: Vec<AppAsset>,
+    version: &str,
+    templates: Option<&Templates>,
+    remote_file_prefix: &Option<String>,
+    rust_abi: &str,
+) -> Result<Vec<AppAsset>> {
+    if let Some(templates) = templates {
+        match select_binstall_asset(templates, package, version, &assets, rust_abi) {
+            Some(asset) => return Ok(vec![asset]),
+            None => debug!("no asset matches binstall templates, try to find it by name"),
+        }
+    }
+    if provider.is_platform_specific() {
+        return Ok(assets);
+    }
+    select_assets(
+        assets,
+        &Platform::current(rust_abi),
+        remote_file_prefix,
+        package.asset_pattern.as_ref(),
+    )
+}
+
+/// find the release of a version, which could also be a semver requirement, e.g. '^0.18' or '>=4.8,<5'.
+/// for a requirement, the highest compatible version is chosen, prereleases are included only if `prerelease` is true.
+pub async fn find_release<P: ReleaseProvider>(
+    provider: &P,
+    version: &str,
+    tag_format: &TagFormat,
+    prerelease: bool,
+) -> Result<Option<RemoteRelease>> {
+    if version == LATEST_VERSION && prerelease {
+        let mut page = 1;
+        loop {
+            let releases = provider.releases_page(page).await?;
+            if releases.is_empty() {
+                return Ok(None);
+            }
+            if let Some(r) = releases
+                .into_iter()
+                .find(|r| !r.draft && tag_format.version_of(&r.tag_name).is_some())
+            {
+                return Ok(Some(r));
+            }
+            page += 1;
+        }
+    }
+    let requirement = match parse_requirement(version) {
+        Some(r) => r,
+        None => return provider.find_release(version).await,
+    };
+    // pages are sorted by publish time, not by version, e.g. a backport published later,
+    // so the highest match could be on any page
+    let mut invalid_tags = vec![];
+    let mut best: Option<(Version, RemoteRelease)> = None;
+    let mut page = 1;
+    loop {
+        let releases = provider.releases_page(page).await?;
+        if releases.is_empty() {
+            break;
+        }
+        for release in releases {
+            if release.draft || (release.prerelease && !prerelease) {
+                continue;
+            }
+            let release_version = match tag_format
+                .version_of(&release.tag_name)
+                .and_then(|v| Version::parse(&v).ok())
+            {
+                Some(v) => v,
+                None => {
+                    invalid_tags.push(release.tag_name);
+                    continue;
                 }
             };
+            if !matches_requirement(&requirement, &release_version, prerelease) {
+                continue;
+            }
+            let higher = match &best {
+                Some((v, _)) => release_version > *v,
+                None => true,
+            };
+            if higher {
+                best = Some((release_version, release));
+            }
+        }
+        page += 1;
+    }
+    match best {
+        Some((v, release)) => {
+            debug!("version {} matches {}", v, requirement);
+            Ok(Some(release))
+        }
+        None if !invalid_tags.is_empty() => Err(InstallError::General(format!(
+            "no release matches {}, the following tags are not semver: {}",
+            requirement,
+            invalid_tags.join(", ")
+        ))),
+        None => Ok(None),
+    }
+}
 
-            let assets: Vec<_> = release
-                .assets
-                .iter()
-                .filter(|a| {
-                    let segments: HashSet<_> = a.name.split(&['-', '.']).collect();
-                    segments.contains(to_github_arch(ARCH))
-                        && segments.contains(to_github_os(OS))
-                        && segments.contains(rust_abi)
-                        && (remote_file_prefix.is_none()
-                            || remote_file_prefix
-                                .as_ref()
-                                .is_some_and(|r| segments.contains(r.as_str())))
-                })
-                .map(|a| AppAsset {
-                    name: a.name.clone(),
-                    download_url: a.browser_download_url.to_string(),
-                })
-                .collect();
+/// semver only matches a prerelease if the requirement has one, e.g. '^1.2.0-rc.1',
+/// so the prerelease part is ignored when prereleases are wanted.
+fn matches_requirement(requirement: &VersionReq, version: &Version, prerelease: bool) -> bool {
+    if requirement.matches(version) {
+        return true;
+    }
+    if !prerelease || version.pre.is_empty() {
+        return false;
+    }
+    let mut stable = version.clone();
+    stable.pre = Prerelease::EMPTY;
+    requirement.matches(&stable)
+}
 
-            Ok(AppRelease {
-                version: release.tag_name.clone(),
-                source_url: release.zipball_url.map(|u| u.to_string()),
-                assets,
-            })
-        }
-        None => Err(InstallError::GeneralStr(
-            "currently only support to install from github.com",
-        )),
+/// None if the version is the latest, an exact version, or not a range at all(e.g. 'nightly' or '2024.1').
+/// a version is a range only if it has an operator or a wildcard, so a bare number like '14' is an exact tag.
+fn parse_requirement(version: &str) -> Option<VersionReq> {
+    if version == LATEST_VERSION || !is_range(version) {
+        return None;
     }
+    VersionReq::parse(normalize_version(version)).ok()
+}
+
+fn is_range(version: &str) -> bool {
+    version.contains(['^', '~', '<', '>', '=', '*', ','])
+        || version.split('.').any(|p| p == "x" || p == "X")
 }
 
 #[derive(Clone, Debug)]
 pub struct AppRelease {
+    /// the version without any prefix of the tag name, it's the name of installed directory.
     pub version: String,
-    pub source_url: Option<String>,
+    pub source: Option<AppSource>,
     pub assets: Vec<AppAsset>,
+    /// the version and publish time, they tell a republished asset from the cached one of the same url.
+    /// None if it's unknown, then any cached file of the url could be used.
+    pub identity: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct AppSource {
+    /// download url
+    pub url: String,
+    /// archive file name, its extension decides how to decompress it.
+    pub file_name: String,
 }
 
 #[derive(Clone, Debug)]
@@ -100,64 +262,52 @@ pub struct AppAsset {
     pub name: String,
     /// download url
     pub download_url: String,
+    /// path of the executable file in the archive, it's guessed from package if it's not specified.
+    pub bin_path: Option<String>,
 }
 
-async fn find_github_release(gt: &Github, version: &str) -> Result<Option<Release>> {
-    let client = octocrab::instance();
-    let mut page: u32 = 0;
-    // assume that tag name is the version, or `v` + version
-    let mut versions = vec![version.to_string()];
-    if version != LATEST_VERSION && !version.starts_with('v') {
-        versions.push(format!("v{}", version));
+/// record an installed version as the current one, a failure doesn't fail the installation.
+pub fn save_receipt(app_dir: &AppDir, package: &Package, receipt: Receipt) {
+    let result = PackageReceipts::load(app_dir, &package.name).and_then(|mut receipts| {
+        receipts.record(receipt);
+        receipts.save(app_dir)
+    });
+    if let Err(e) = result {
+        error!("failed to save the receipt of {}: {}", package.name, e);
     }
-    loop {
-        match client
-            .repos(&gt.org, &gt.repo)
-            .releases()
-            .list()
-            .page(page)
-            .per_page(10)
-            .send()
-            .await
-        {
-            Ok(s) => {
-                if s.items.is_empty() {
-                    return Ok(None);
-                }
-                // filter by draft and prerelease
-                match s.items.into_iter().find(|r| {
-                    if version == LATEST_VERSION {
-                        !(r.draft || r.prerelease)
-                    } else {
-                        // TODO maybe the tag_name format can be configured in kaleido.toml
-                        versions.contains(&r.tag_name)
-                    }
-                }) {
-                    Some(r) => return Ok(Some(r)),
-                    None => {
-                        page += 1;
-                        debug!("load next page releases: {}", page);
-                    }
-                };
-            }
-            Err(e) => {
-                return Err(InstallError::General(format!(
-                    "failed to load releases: {}",
-                    e
-                )));
-            }
-        }
+}
+
+/// the target triple of current platform, e.g. 'x86_64-unknown-linux-gnu'.
+pub fn rust_target(rust_abi: &str) -> String {
+    target_triple(OS, ARCH, rust_abi)
+}
+
+/// keys of `Package.platforms` for current platform, the most specific one is the first.
+pub fn platform_keys(rust_abi: &str) -> Vec<String> {
+    vec![
+        rust_target(rust_abi),
+        format!("{}-{}", ARCH, OS),
+        OS.to_string(),
+    ]
+}
+
+pub fn target_triple(os: &str, arch: &str, rust_abi: &str) -> String {
+    match os {
+        "linux" => format!("{}-unknown-linux-{}", arch, rust_abi),
+        "macos" => format!("{}-apple-darwin", arch),
+        "windows" => format!("{}-pc-windows-{}", arch, rust_abi),
+        _ => format!("{}-unknown-{}", arch, os),
     }
 }
 
-fn to_github_os(os: &str) -> &str {
+pub fn to_github_os(os: &str) -> &str {
     match os {
         "macos" => "darwin",
         _ => os,
     }
 }
 
-fn to_github_arch(arch: &str) -> &str {
+pub fn to_github_arch(arch: &str) -> &str {
     arch
 }
 
@@ -171,8 +321,173 @@ pub enum InstallError {
     Http(#[from] http::HttpError),
     #[error("{0}")]
     FileSystem(#[from] std::io::Error),
+    #[error("failed to load {0}: {1}")]
+    Fetch(&'static str, http::HttpError),
     #[error("{0}")]
     General(String),
     #[error("{0}")]
     GeneralStr(&'static str),
 }
+
+impl InstallError {
+    /// the server cannot be reached or it fails, unlike errors of configurations or releases.
+    pub fn is_network(&self) -> bool {
+        match self {
+            InstallError::Reqwest(_) => true,
+            InstallError::Http(e) | InstallError::Fetch(_, e) => {
+                !matches!(e, http::HttpError::FileSystem(_))
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::action::installer::provider::MockReleaseProvider;
+
+    fn provider(tags: &[(&str, bool)]) -> MockReleaseProvider {
+        let releases: Vec<RemoteRelease> = tags
+            .iter()
+            .map(|(t, prerelease)| RemoteRelease {
+                tag_name: t.to_string(),
+                prerelease: *prerelease,
+                ..Default::default()
+            })
+            .collect();
+        let mut provider = MockReleaseProvider::new();
+        provider
+            .expect_releases_page()
+            .returning(move |p| Ok(if p == 1 { releases.clone() } else { vec![] }));
+        provider.expect_find_release().returning(|v| {
+            Ok(Some(RemoteRelease {
+                tag_name: v.to_string(),
+                ..Default::default()
+            }))
+        });
+        provider
+    }
+
+    #[tokio::test]
+    async fn test_find_release_by_requirement() {
+        let provider = provider(&[
+            ("v0.19.0-beta.1", true),
+            ("v0.18.2", false),
+            ("v0.18.10", false),
+            ("v0.17.0", false),
+            ("v1.2.9", false),
+            ("v1.3.0", false),
+            ("v4.8.1", false),
+            ("v5.0.0", false),
+        ]);
+        let format = TagFormat::new(None, "tool");
+        let cases = [
+            ("^0.18", "v0.18.10"),
+            ("~1.2", "v1.2.9"),
+            (">=4.8,<5", "v4.8.1"),
+            ("*", "v5.0.0"),
+            ("1.3.0", "1.3.0"),
+            // bare numbers are exact tags, not ranges
+            ("14", "14"),
+            ("2024.1", "2024.1"),
+            ("1.x", "v1.3.0"),
+            (LATEST_VERSION, LATEST_VERSION),
+        ];
+        for (requirement, expected) in cases {
+            let release = find_release(&provider, requirement, &format, false)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(expected, release.tag_name, "{}", requirement);
+        }
+        let missing = find_release(&provider, "^2", &format, false).await.unwrap();
+        assert!(missing.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_requirement_without_semver_tags() {
+        let provider = provider(&[("nightly", false), ("release-2024", false)]);
+        let format = TagFormat::new(None, "tool");
+
+        let err = find_release(&provider, "^1", &format, false).await.unwrap_err();
+        assert!(err.to_string().contains("nightly, release-2024"));
+        let nightly = find_release(&provider, "nightly", &format, false)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!("nightly", nightly.tag_name);
+    }
+
+    #[tokio::test]
+    async fn test_find_prerelease() {
+        let provider = provider(&[("v0.19.0-beta.1", true), ("v0.18.2", false)]);
+        let format = TagFormat::new(None, "tool");
+        let cases = [
+            (LATEST_VERSION, true, "v0.19.0-beta.1"),
+            (LATEST_VERSION, false, LATEST_VERSION),
+            ("^0.19", true, "v0.19.0-beta.1"),
+            ("^0.18", false, "v0.18.2"),
+        ];
+        for (version, prerelease, expected) in cases {
+            let release = find_release(&provider, version, &format, prerelease)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(expected, release.tag_name, "{} {}", version, prerelease);
+        }
+        let stable = find_release(&provider, "^0.19", &format, false).await.unwrap();
+        assert!(stable.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_best_match_on_next_page() {
+        let mut provider = MockReleaseProvider::new();
+        provider.expect_releases_page().times(3).returning(|p| {
+            let tags: &[&str] = match p {
+                1 => &["v2.0.0", "v1.2.0"],
+                // backports published after 1.2.0
+                2 => &["v1.9.0", "v1.8.3"],
+                _ => &[],
+            };
+            Ok(tags
+                .iter()
+                .map(|t| RemoteRelease {
+                    tag_name: t.to_string(),
+                    ..Default::default()
+                })
+                .collect())
+        });
+        let format = TagFormat::new(None, "tool");
+        let release = find_release(&provider, "^1", &format, false)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!("v1.9.0", release.tag_name);
+    }
+
+    #[tokio::test]
+    async fn test_latest_prerelease_on_next_page() {
+        let mut provider = MockReleaseProvider::new();
+        provider.expect_releases_page().times(2).returning(|p| {
+            Ok(match p {
+                1 => vec![RemoteRelease {
+                    tag_name: "v0.3.0-rc.1".to_string(),
+                    draft: true,
+                    ..Default::default()
+                }],
+                _ => vec![RemoteRelease {
+                    tag_name: "v0.2.0-rc.2".to_string(),
+                    prerelease: true,
+                    ..Default::default()
+                }],
+            })
+        });
+        let format = TagFormat::new(None, "tool");
+        let release = find_release(&provider, LATEST_VERSION, &format, true)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!("v0.2.0-rc.2", release.tag_name);
+    }
+}
@@ -1,7 +1,47 @@
+mod asset_selector;
+mod binstall;
 mod install;
 mod execute;
+mod outdated;
+mod provider;
 pub mod rust_bin_installer;
 pub mod rust_src_installer;
+mod transaction;
+mod versions;
 
-pub use execute::{install, InstallRequest};
-pub use install::InstallerContext;
\ No newline at end of file
+pub use execute::{install, InstallRequest, InstallStatus};
+pub use install::{platform_keys, InstallError, InstallerContext};
+pub use outdated::{outdated, OutdatedStatus};
+pub use provider::{get_github_json, normalize_version, GithubRelease, TagFormat};
+pub use transaction::{verify_bin, Transaction};
+pub use versions::versions;
+
+#[cfg(test)]
+mod test {
+    use flate2::{write::GzEncoder, Compression};
+
+    /// a tar.gz of executable scripts, a path ending with '/' is a directory.
+    pub(super) fn archive(paths: &[&str]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(GzEncoder::new(vec![], Compression::default()));
+        for path in paths {
+            let mut header = tar::Header::new_gnu();
+            header.set_mode(0o755);
+            if path.ends_with('/') {
+                header.set_entry_type(tar::EntryType::Directory);
+                header.set_size(0);
+                header.set_cksum();
+                builder
+                    .append_data(&mut header, path, std::io::empty())
+                    .unwrap();
+            } else {
+                let content = format!("#!/bin/sh\necho {}\n", path);
+                header.set_size(content.len() as u64);
+                header.set_cksum();
+                builder
+                    .append_data(&mut header, path, content.as_bytes())
+                    .unwrap();
+            }
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+}
@@ -0,0 +1,222 @@
+use super::{
+    execute::LATEST_VERSION,
+    install::{find_release, platform_keys, Result},
+    provider::{normalize_version, PackageProvider, ReleaseProvider, TagFormat},
+};
+use crate::action::helper::get_installed_packages;
+use crate::tool::{
+    fs::AppDir,
+    kaleido::{Channel, Package},
+    state::{Pin, Pins},
+};
+use colored::Colorize;
+use futures_util::{stream, StreamExt};
+use log::error;
+use semver::Version;
+
+/// how many packages are checked at the same time.
+const CONCURRENCY: usize = 8;
+
+struct OutdatedPackage {
+    name: String,
+    installed: String,
+    latest: Result<Option<String>>,
+    /// the pin in `state/pins.toml`
+    pin: Option<Pin>,
+}
+
+impl OutdatedPackage {
+    fn is_outdated(&self) -> bool {
+        match &self.latest {
+            Ok(Some(latest)) => is_newer(&self.installed, latest),
+            _ => false,
+        }
+    }
+}
+
+/// result of checking installed packages, it decides the exit code.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutdatedStatus {
+    UpToDate,
+    Outdated,
+    /// the latest release of any package cannot be found
+    Failed,
+}
+
+impl OutdatedStatus {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            OutdatedStatus::UpToDate => 0,
+            OutdatedStatus::Outdated => 1,
+            OutdatedStatus::Failed => 2,
+        }
+    }
+}
+
+/// print installed packages which have newer releases.
+pub async fn outdated(app_dir: &AppDir, rust_abi: &str) -> OutdatedStatus {
+    let installed_packages = match get_installed_packages(app_dir).await {
+        Ok(p) => p,
+        Err(e) => {
+            error!("Failed to get installed packages: {}", e);
+            return OutdatedStatus::Failed;
+        }
+    };
+
+    let pins = match Pins::load(app_dir) {
+        Ok(p) => p,
+        Err(e) => {
+            error!("Failed to load pins: {}", e);
+            Pins::default()
+        }
+    };
+
+    let keys = platform_keys(rust_abi);
+    let packages: Vec<OutdatedPackage> = stream::iter(installed_packages)
+        .map(|ip| {
+            let package = ip.package.for_platform(&keys);
+            let pin = pins.get(&package.name).cloned();
+            async move {
+                let latest = match PackageProvider::from_package(&package, rust_abi) {
+                    Ok(provider) => latest_version(&provider, &package).await,
+                    Err(e) => Err(e),
+                };
+                OutdatedPackage {
+                    name: package.name.clone(),
+                    installed: normalize_version(&ip.version).to_string(),
+                    latest,
+                    pin,
+                }
+            }
+        })
+        .buffered(CONCURRENCY)
+        .collect()
+        .await;
+
+    let mut any_outdated = false;
+    let mut any_failed = false;
+    for p in &packages {
+        let pinned = match &p.pin {
+            Some(Pin { version: Some(v) }) => normalize_version(v).to_string(),
+            Some(_) => "yes".to_string(),
+            None => "-".to_string(),
+        };
+        match &p.latest {
+            Ok(latest) => {
+                let latest = latest.as_deref().unwrap_or("-");
+                if p.is_outdated() {
+                    any_outdated = true;
+                    println!(
+                        "* {:<20} {:<15} -> {:<15} (pinned: {})",
+                        p.name.yellow(),
+                        p.installed,
+                        latest.yellow(),
+                        pinned
+                    );
+                } else {
+                    println!(
+                        "  {:<20} {:<15}    {:<15} (pinned: {})",
+                        p.name.green(),
+                        p.installed,
+                        latest,
+                        pinned
+                    );
+                }
+            }
+            Err(e) => {
+                any_failed = true;
+                println!(
+                    "  {:<20} {:<15} {}",
+                    p.name,
+                    p.installed,
+                    format!("failed to find the latest release: {}", e).red()
+                );
+            }
+        }
+    }
+    if any_failed {
+        OutdatedStatus::Failed
+    } else if any_outdated {
+        OutdatedStatus::Outdated
+    } else {
+        OutdatedStatus::UpToDate
+    }
+}
+
+/// the latest version of the package's channel.
+async fn latest_version<P: ReleaseProvider>(
+    provider: &P,
+    package: &Package,
+) -> Result<Option<String>> {
+    let tag_format = TagFormat::from_package(package);
+    let prerelease = package.channel == Some(Channel::Prerelease);
+    let release = find_release(provider, LATEST_VERSION, &tag_format, prerelease).await?;
+    Ok(release.map(|r| {
+        tag_format
+            .version_of(&r.tag_name)
+            .unwrap_or_else(|| normalize_version(&r.tag_name).to_string())
+    }))
+}
+
+/// compare as semver if possible, otherwise any different version is newer.
+fn is_newer(installed: &str, latest: &str) -> bool {
+    let installed = normalize_version(installed);
+    let latest = normalize_version(latest);
+    match (Version::parse(installed), Version::parse(latest)) {
+        (Ok(i), Ok(l)) => l > i,
+        _ => installed != latest,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::action::installer::provider::{MockReleaseProvider, RemoteRelease};
+
+    #[test]
+    fn test_is_newer() {
+        let cases = [
+            ("1.0.0", "1.1.0", true),
+            ("v1.0.0", "1.0.0", false),
+            ("1.10.0", "1.9.0", false),
+            ("1.0.0-rc.1", "1.0.0", true),
+            ("nightly-1", "nightly-2", true),
+            ("nightly-1", "nightly-1", false),
+        ];
+        for (installed, latest, expected) in cases {
+            assert_eq!(
+                expected,
+                is_newer(installed, latest),
+                "{} {}",
+                installed,
+                latest
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_latest_version() {
+        let mut provider = MockReleaseProvider::new();
+        provider
+            .expect_find_release()
+            .withf(|v| v == LATEST_VERSION)
+            .returning(|_| {
+                Ok(Some(RemoteRelease {
+                    tag_name: "tool-v1.2.0".to_string(),
+                    ..Default::default()
+                }))
+            });
+        let package: Package = toml::from_str(
+            r#"
+            name = "tool"
+            url = "https://example.com/tool"
+            bin_name = "tool"
+            tag_format = "{name}-v{version}"
+            "#,
+        )
+        .unwrap();
+
+        let latest = latest_version(&provider, &package).await.unwrap();
+        assert_eq!(Some("1.2.0".to_string()), latest);
+    }
+}
@@ -0,0 +1,242 @@
+use super::{ReleaseProvider, RemoteRelease, TagFormat};
+use crate::action::installer::execute::LATEST_VERSION;
+use crate::action::installer::install::{AppAsset, AppSource, InstallError, Result};
+use crate::tool::{
+    http::{get_json, get_text},
+    kaleido::CratesIo,
+};
+use log::debug;
+use reqwest::header::HeaderMap;
+use semver::Version;
+use serde::Deserialize;
+
+const CRATES_IO_INDEX: &str = "https://index.crates.io";
+
+#[derive(Deserialize, Debug)]
+struct RegistryConfig {
+    dl: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct IndexEntry {
+    name: String,
+    vers: String,
+    #[serde(default)]
+    cksum: String,
+    #[serde(default)]
+    yanked: bool,
+}
+
+pub struct CratesIoProvider {
+    crate_name: String,
+    index: String,
+}
+
+impl CratesIoProvider {
+    pub fn new(crates_io: CratesIo, package_name: &str) -> Self {
+        CratesIoProvider {
+            crate_name: crates_io
+                .crate_name
+                .unwrap_or_else(|| package_name.to_string()),
+            index: crates_io
+                .index
+                .unwrap_or_else(|| CRATES_IO_INDEX.to_string())
+                .trim_end_matches('/')
+                .to_string(),
+        }
+    }
+
+    /// all versions in the index which are not yanked, the latest one is the first.
+    async fn load_versions(&self) -> Result<Vec<RemoteRelease>> {
+        let config: RegistryConfig =
+            get_json(&format!("{}/config.json", self.index), HeaderMap::new())
+                .await
+                .map_err(|e| InstallError::Fetch("registry config", e))?;
+        let name = self.crate_name.to_lowercase();
+        let content = get_text(&format!("{}/{}/{}", self.index, prefix(&name), name))
+            .await
+            .map_err(|e| InstallError::Fetch("crate index", e))?;
+
+        let mut versions = vec![];
+        for line in content.lines().filter(|l| !l.trim().is_empty()) {
+            let entry: IndexEntry = match serde_json::from_str(line) {
+                Ok(e) => e,
+                Err(e) => {
+                    debug!("invalid index entry {}: {}", line, e);
+                    continue;
+                }
+            };
+            if entry.yanked {
+                continue;
+            }
+            match Version::parse(&entry.vers) {
+                Ok(v) => versions.push((v, entry)),
+                Err(e) => debug!("invalid crate version {}: {}", entry.vers, e),
+            }
+        }
+        versions.sort_by(|a, b| b.0.cmp(&a.0));
+
+        Ok(versions
+            .into_iter()
+            .map(|(v, entry)| RemoteRelease {
+                tag_name: entry.vers.clone(),
+                prerelease: !v.pre.is_empty(),
+                source: Some(AppSource {
+                    url: download_url(&config.dl, &entry),
+                    file_name: format!("{}-{}.crate", entry.name, entry.vers),
+                }),
+                ..Default::default()
+            })
+            .collect())
+    }
+}
+
+impl ReleaseProvider for CratesIoProvider {
+    async fn list_releases(&self) -> Result<Vec<RemoteRelease>> {
+        self.load_versions().await
+    }
+
+    async fn find_release(&self, version: &str) -> Result<Option<RemoteRelease>> {
+        let versions = TagFormat::new(None, &self.crate_name).tags(version);
+        Ok(self.load_versions().await?.into_iter().find(|r| {
+            if version == LATEST_VERSION {
+                !r.prerelease
+            } else {
+                versions.contains(&r.tag_name)
+            }
+        }))
+    }
+
+    /// a registry only has source code.
+    async fn assets(&self, _release: &RemoteRelease) -> Result<Vec<AppAsset>> {
+        Ok(vec![])
+    }
+}
+
+/// the directory of a crate in the index, see https://doc.rust-lang.org/cargo/reference/registry-index.html#index-files
+fn prefix(name: &str) -> String {
+    match name.len() {
+        1 => "1".to_string(),
+        2 => "2".to_string(),
+        3 => format!("3/{}", &name[0..1]),
+        _ => format!("{}/{}", &name[0..2], &name[2..4]),
+    }
+}
+
+fn download_url(dl: &str, entry: &IndexEntry) -> String {
+    let markers = [
+        "{crate}",
+        "{version}",
+        "{prefix}",
+        "{lowerprefix}",
+        "{sha256-checksum}",
+    ];
+    if !markers.iter().any(|m| dl.contains(m)) {
+        return format!(
+            "{}/{}/{}/download",
+            dl.trim_end_matches('/'),
+            entry.name,
+            entry.vers
+        );
+    }
+    let lower_name = entry.name.to_lowercase();
+    dl.replace("{crate}", &entry.name)
+        .replace("{version}", &entry.vers)
+        .replace("{prefix}", &prefix(&entry.name))
+        .replace("{lowerprefix}", &prefix(&lower_name))
+        .replace("{sha256-checksum}", &entry.cksum)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mockito::{Server, ServerGuard};
+
+    const INDEX: &str = r#"{"name":"tool","vers":"0.9.0","deps":[],"cksum":"a1","features":{},"yanked":false}
+{"name":"tool","vers":"1.1.0","deps":[],"cksum":"b2","features":{},"yanked":true}
+{"name":"tool","vers":"1.0.0","deps":[],"cksum":"c3","features":{},"yanked":false}
+{"name":"tool","vers":"1.2.0-beta.1","deps":[],"cksum":"d4","features":{},"yanked":false}
+"#;
+
+    async fn registry(dl: &str) -> (ServerGuard, CratesIoProvider) {
+        let mut server = Server::new_async().await;
+        let dl = dl.replace("{server}", &server.url());
+        server
+            .mock("GET", "/config.json")
+            .with_body(format!(r#"{{"dl": "{}", "api": "{}"}}"#, dl, server.url()))
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/to/ol/tool")
+            .with_body(INDEX)
+            .create_async()
+            .await;
+        let provider = CratesIoProvider::new(
+            CratesIo {
+                crate_name: None,
+                index: Some(format!("{}/", server.url())),
+            },
+            "tool",
+        );
+        (server, provider)
+    }
+
+    #[test]
+    fn test_prefix() {
+        assert_eq!("1", prefix("a"));
+        assert_eq!("2", prefix("ab"));
+        assert_eq!("3/a", prefix("abc"));
+        assert_eq!("ri/pg", prefix("ripgrep"));
+    }
+
+    #[tokio::test]
+    async fn test_list_releases() {
+        let (_server, provider) = registry("{server}/dl").await;
+
+        let releases = provider.list_releases().await.unwrap();
+        let tags: Vec<_> = releases.iter().map(|r| r.tag_name.as_str()).collect();
+        assert_eq!(vec!["1.2.0-beta.1", "1.0.0", "0.9.0"], tags);
+        assert!(releases[0].prerelease);
+    }
+
+    #[tokio::test]
+    async fn test_find_release() {
+        let (server, provider) = registry("{server}/dl").await;
+
+        let latest = provider
+            .find_release(LATEST_VERSION)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!("1.0.0", latest.tag_name);
+        let source = latest.source.unwrap();
+        assert_eq!(
+            format!("{}/dl/tool/1.0.0/download", server.url()),
+            source.url
+        );
+        assert_eq!("tool-1.0.0.crate", source.file_name);
+
+        let old = provider.find_release("v0.9.0").await.unwrap().unwrap();
+        assert_eq!("0.9.0", old.tag_name);
+        let yanked = provider.find_release("1.1.0").await.unwrap();
+        assert!(yanked.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_download_url_template() {
+        let (_server, provider) = registry(
+            "https://mirror.local/{prefix}/{crate}/{crate}-{version}.crate?sum={sha256-checksum}",
+        )
+        .await;
+
+        let latest = provider
+            .find_release(LATEST_VERSION)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            "https://mirror.local/to/ol/tool/tool-1.0.0.crate?sum=c3",
+            latest.source.unwrap().url
+        );
+    }
+}
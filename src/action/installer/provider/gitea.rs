@@ -0,0 +1,177 @@
+use super::{collect_pages, find_in_pages, ReleaseProvider, RemoteRelease, TagFormat};
+use crate::action::installer::install::{AppAsset, AppSource, InstallError, Result};
+use crate::tool::{http::get_json, kaleido::Gitea};
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use serde::Deserialize;
+
+const PER_PAGE: u32 = 10;
+
+#[derive(Deserialize, Debug)]
+struct GiteaRelease {
+    tag_name: String,
+    #[serde(default)]
+    draft: bool,
+    #[serde(default)]
+    prerelease: bool,
+    published_at: Option<String>,
+    zipball_url: Option<String>,
+    #[serde(default)]
+    assets: Vec<GiteaAsset>,
+}
+
+#[derive(Deserialize, Debug)]
+struct GiteaAsset {
+    name: String,
+    browser_download_url: String,
+}
+
+impl From<GiteaRelease> for RemoteRelease {
+    fn from(r: GiteaRelease) -> Self {
+        RemoteRelease {
+            tag_name: r.tag_name.clone(),
+            draft: r.draft,
+            prerelease: r.prerelease,
+            published_at: r.published_at,
+            source: r.zipball_url.map(|url| AppSource {
+                url,
+                file_name: format!("{}.zip", r.tag_name),
+            }),
+            assets: r
+                .assets
+                .into_iter()
+                .map(|a| AppAsset {
+                    name: a.name,
+                    download_url: a.browser_download_url,
+                    bin_path: None,
+                })
+                .collect(),
+        }
+    }
+}
+
+pub struct GiteaProvider {
+    gitea: Gitea,
+    tag_format: TagFormat,
+}
+
+impl GiteaProvider {
+    pub fn new(gitea: Gitea, tag_format: TagFormat) -> Self {
+        GiteaProvider { gitea, tag_format }
+    }
+
+    fn headers(&self) -> Result<HeaderMap> {
+        let mut headers = HeaderMap::new();
+        if let Some(token) = self.gitea.token.as_ref() {
+            let value = HeaderValue::from_str(&format!("token {}", token))
+                .map_err(|_| InstallError::GeneralStr("invalid gitea token"))?;
+            headers.insert(AUTHORIZATION, value);
+        }
+        Ok(headers)
+    }
+
+    async fn load_page(&self, page: u32) -> Result<Vec<RemoteRelease>> {
+        let url = format!(
+            "{}/api/v1/repos/{}/{}/releases?page={}&limit={}",
+            self.gitea.base_url.trim_end_matches('/'),
+            self.gitea.org,
+            self.gitea.repo,
+            page,
+            PER_PAGE
+        );
+        let releases: Vec<GiteaRelease> = get_json(&url, self.headers()?)
+            .await
+            .map_err(|e| InstallError::Fetch("releases", e))?;
+        Ok(releases.into_iter().map(|r| r.into()).collect())
+    }
+}
+
+impl ReleaseProvider for GiteaProvider {
+    async fn list_releases(&self) -> Result<Vec<RemoteRelease>> {
+        collect_pages(|page| self.load_page(page)).await
+    }
+
+    async fn releases_page(&self, page: u32) -> Result<Vec<RemoteRelease>> {
+        self.load_page(page).await
+    }
+
+    async fn find_release(&self, version: &str) -> Result<Option<RemoteRelease>> {
+        find_in_pages(version, &self.tag_format, |page| self.load_page(page)).await
+    }
+
+    async fn assets(&self, release: &RemoteRelease) -> Result<Vec<AppAsset>> {
+        Ok(release.assets.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::action::installer::execute::LATEST_VERSION;
+    use crate::action::installer::provider::test::{check_pages, mock_pages};
+    use mockito::{Matcher, Server};
+
+    const PAGE_1: &str = r#"[
+        {"tag_name": "v2.0.0-rc.1", "draft": false, "prerelease": true, "assets": []},
+        {"tag_name": "v1.3.0", "draft": true, "prerelease": false, "assets": []},
+        {"tag_name": "v1.2.0", "draft": false, "prerelease": false,
+         "zipball_url": "https://forge.local/org/tool/archive/v1.2.0.zip",
+         "assets": [{"name": "tool-x86_64-linux-gnu.tar.gz",
+                     "browser_download_url": "https://forge.local/tool-x86_64-linux-gnu.tar.gz"}]}
+    ]"#;
+    const PAGE_2: &str = r#"[
+        {"tag_name": "v1.0.0", "draft": false, "prerelease": false, "assets": []}
+    ]"#;
+
+    fn provider(server: &Server, token: Option<&str>) -> GiteaProvider {
+        GiteaProvider::new(
+            Gitea {
+                base_url: server.url(),
+                org: "org".to_string(),
+                repo: "tool".to_string(),
+                token: token.map(|t| t.to_string()),
+            },
+            TagFormat::new(None, "tool"),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_pages() {
+        let mut server = Server::new_async().await;
+        mock_pages(
+            &mut server,
+            "/api/v1/repos/org/tool/releases",
+            &[PAGE_1, PAGE_2],
+        )
+        .await;
+
+        let (release, old) = check_pages(&provider(&server, None)).await;
+        let source = release.source.unwrap();
+        assert_eq!(
+            "https://forge.local/org/tool/archive/v1.2.0.zip",
+            source.url
+        );
+        assert_eq!("v1.2.0.zip", source.file_name);
+        assert_eq!(1, release.assets.len());
+        assert_eq!("tool-x86_64-linux-gnu.tar.gz", release.assets[0].name);
+        assert!(old.assets.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_send_token() {
+        let mut server = Server::new_async().await;
+        let mock = server
+            .mock("GET", "/api/v1/repos/org/tool/releases")
+            .match_query(Matcher::Any)
+            .match_header("authorization", "token secret")
+            .with_body("[]")
+            .create_async()
+            .await;
+
+        let release = provider(&server, Some("secret"))
+            .find_release(LATEST_VERSION)
+            .await
+            .unwrap();
+        assert!(release.is_none());
+        mock.assert_async().await;
+    }
+}
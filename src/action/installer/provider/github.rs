@@ -0,0 +1,215 @@
+use super::{collect_pages, find_in_pages, ReleaseProvider, RemoteRelease, TagFormat};
+use crate::action::installer::install::{AppAsset, AppSource, InstallError, Result};
+use crate::tool::{
+    http::{self, get_json},
+    kaleido::Github,
+};
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT};
+use serde::{de::DeserializeOwned, Deserialize};
+
+const API_URL: &str = "https://api.github.com";
+const PER_PAGE: u8 = 10;
+
+#[derive(Deserialize, Debug)]
+pub struct GithubRelease {
+    pub tag_name: String,
+    #[serde(default)]
+    pub draft: bool,
+    #[serde(default)]
+    pub prerelease: bool,
+    pub published_at: Option<String>,
+    pub zipball_url: Option<String>,
+    #[serde(default)]
+    pub assets: Vec<GithubAsset>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct GithubAsset {
+    pub name: String,
+    pub browser_download_url: String,
+}
+
+/// get json from github api, e.g. 'repos/jinyuli/sys-kaleido/releases/latest'.
+pub async fn get_github_json<T: DeserializeOwned>(path: &str) -> http::Result<T> {
+    github_json(API_URL, path).await
+}
+
+async fn github_json<T: DeserializeOwned>(api_url: &str, path: &str) -> http::Result<T> {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        ACCEPT,
+        HeaderValue::from_static("application/vnd.github+json"),
+    );
+    get_json(&format!("{}/{}", api_url, path), headers).await
+}
+
+pub struct GithubProvider {
+    github: Github,
+    tag_format: TagFormat,
+    api_url: String,
+}
+
+impl GithubProvider {
+    pub fn new(github: Github, tag_format: TagFormat) -> Self {
+        GithubProvider {
+            github,
+            tag_format,
+            api_url: API_URL.to_string(),
+        }
+    }
+
+    async fn load_page(&self, page: u32) -> Result<Vec<RemoteRelease>> {
+        let path = format!(
+            "repos/{}/{}/releases?page={}&per_page={}",
+            self.github.org, self.github.repo, page, PER_PAGE
+        );
+        let releases: Vec<GithubRelease> = github_json(&self.api_url, &path)
+            .await
+            .map_err(|e| InstallError::Fetch("releases", e))?;
+        Ok(releases.into_iter().map(|r| r.into()).collect())
+    }
+}
+
+impl From<GithubRelease> for RemoteRelease {
+    fn from(r: GithubRelease) -> Self {
+        RemoteRelease {
+            tag_name: r.tag_name.clone(),
+            draft: r.draft,
+            prerelease: r.prerelease,
+            published_at: r.published_at,
+            source: r.zipball_url.map(|url| AppSource {
+                url,
+                file_name: format!("{}.zip", r.tag_name),
+            }),
+            assets: r
+                .assets
+                .into_iter()
+                .map(|a| AppAsset {
+                    name: a.name,
+                    download_url: a.browser_download_url,
+                    bin_path: None,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl ReleaseProvider for GithubProvider {
+    async fn list_releases(&self) -> Result<Vec<RemoteRelease>> {
+        collect_pages(|page| self.load_page(page)).await
+    }
+
+    async fn releases_page(&self, page: u32) -> Result<Vec<RemoteRelease>> {
+        self.load_page(page).await
+    }
+
+    async fn find_release(&self, version: &str) -> Result<Option<RemoteRelease>> {
+        find_in_pages(version, &self.tag_format, |page| self.load_page(page)).await
+    }
+
+    async fn assets(&self, release: &RemoteRelease) -> Result<Vec<AppAsset>> {
+        Ok(release.assets.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::action::installer::execute::LATEST_VERSION;
+    use crate::action::installer::provider::test::{check_pages, mock_pages};
+    use mockito::{Matcher, Server};
+
+    const PAGE_1: &str = r#"[
+        {"tag_name": "v1.3.0", "draft": true, "prerelease": false, "assets": []},
+        {"tag_name": "v1.2.0", "draft": false, "prerelease": false,
+         "published_at": "2024-01-01T00:00:00Z",
+         "zipball_url": "https://api.github.com/repos/org/tool/zipball/v1.2.0",
+         "assets": [{"name": "tool-x86_64-unknown-linux-gnu.tar.gz",
+                     "browser_download_url": "https://github.com/org/tool/releases/download/v1.2.0/tool-x86_64-unknown-linux-gnu.tar.gz"}]}
+    ]"#;
+    const PAGE_2: &str = r#"[
+        {"tag_name": "v1.0.0", "assets": []}
+    ]"#;
+
+    fn provider(server: &Server) -> GithubProvider {
+        GithubProvider {
+            github: Github {
+                org: "org".to_string(),
+                repo: "tool".to_string(),
+            },
+            tag_format: TagFormat::new(None, "tool"),
+            api_url: server.url(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pages() {
+        let mut server = Server::new_async().await;
+        mock_pages(&mut server, "/repos/org/tool/releases", &[PAGE_1, PAGE_2]).await;
+
+        let (release, old) = check_pages(&provider(&server)).await;
+        assert_eq!(
+            Some("2024-01-01T00:00:00Z"),
+            release.published_at.as_deref()
+        );
+        let source = release.source.unwrap();
+        assert_eq!(
+            "https://api.github.com/repos/org/tool/zipball/v1.2.0",
+            source.url
+        );
+        assert_eq!("v1.2.0.zip", source.file_name);
+        assert_eq!(
+            "https://github.com/org/tool/releases/download/v1.2.0/tool-x86_64-unknown-linux-gnu.tar.gz",
+            release.assets[0].download_url
+        );
+        assert!(old.source.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_request() {
+        let mut server = Server::new_async().await;
+        let mock = server
+            .mock("GET", "/repos/org/tool/releases")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("page".into(), "2".into()),
+                Matcher::UrlEncoded("per_page".into(), PER_PAGE.to_string()),
+            ]))
+            .match_header("accept", "application/vnd.github+json")
+            .with_body(PAGE_2)
+            .create_async()
+            .await;
+
+        let releases = provider(&server).releases_page(2).await.unwrap();
+        assert_eq!("v1.0.0", releases[0].tag_name);
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_errors() {
+        let mut server = Server::new_async().await;
+        server
+            .mock("GET", "/repos/org/tool/releases")
+            .match_query(Matcher::Any)
+            .with_status(404)
+            .with_body(r#"{"message": "Not Found"}"#)
+            .create_async()
+            .await;
+        assert!(provider(&server)
+            .find_release(LATEST_VERSION)
+            .await
+            .is_err());
+
+        // rate limited
+        server.reset();
+        server
+            .mock("GET", "/repos/org/tool/releases")
+            .match_query(Matcher::Any)
+            .with_status(403)
+            .with_header("x-ratelimit-remaining", "0")
+            .with_body(r#"{"message": "API rate limit exceeded"}"#)
+            .create_async()
+            .await;
+        let e = provider(&server).list_releases().await.unwrap_err();
+        assert!(e.to_string().contains("403"));
+    }
+}
@@ -0,0 +1,196 @@
+use super::{collect_pages, find_in_pages, ReleaseProvider, RemoteRelease, TagFormat};
+use crate::action::installer::install::{AppAsset, AppSource, InstallError, Result};
+use crate::tool::{http::get_json, kaleido::Gitlab};
+use reqwest::header::HeaderMap;
+use serde::Deserialize;
+
+const GITLAB_HOST: &str = "https://gitlab.com";
+const PER_PAGE: u32 = 20;
+
+#[derive(Deserialize, Debug)]
+struct GitlabRelease {
+    tag_name: String,
+    #[serde(default)]
+    upcoming_release: bool,
+    released_at: Option<String>,
+    assets: GitlabAssets,
+}
+
+#[derive(Deserialize, Debug)]
+struct GitlabAssets {
+    #[serde(default)]
+    sources: Vec<GitlabSource>,
+    #[serde(default)]
+    links: Vec<GitlabLink>,
+}
+
+#[derive(Deserialize, Debug)]
+struct GitlabSource {
+    format: String,
+    url: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct GitlabLink {
+    name: String,
+    url: String,
+    direct_asset_url: Option<String>,
+}
+
+impl From<GitlabRelease> for RemoteRelease {
+    fn from(r: GitlabRelease) -> Self {
+        RemoteRelease {
+            tag_name: r.tag_name.clone(),
+            // gitlab has no draft or prerelease, an upcoming release is not published yet.
+            draft: r.upcoming_release,
+            prerelease: false,
+            published_at: r.released_at,
+            source: r
+                .assets
+                .sources
+                .into_iter()
+                .find(|s| s.format == "zip")
+                .map(|s| AppSource {
+                    url: s.url,
+                    file_name: format!("{}.zip", r.tag_name),
+                }),
+            assets: r
+                .assets
+                .links
+                .into_iter()
+                .map(|l| AppAsset {
+                    name: l.name,
+                    download_url: l.direct_asset_url.unwrap_or(l.url),
+                    bin_path: None,
+                })
+                .collect(),
+        }
+    }
+}
+
+pub struct GitlabProvider {
+    gitlab: Gitlab,
+    tag_format: TagFormat,
+}
+
+impl GitlabProvider {
+    pub fn new(gitlab: Gitlab, tag_format: TagFormat) -> Self {
+        GitlabProvider { gitlab, tag_format }
+    }
+
+    fn api_url(&self) -> String {
+        let host = match self.gitlab.host.as_deref() {
+            Some(h) if h.contains("://") => h.trim_end_matches('/').to_string(),
+            Some(h) => format!("https://{}", h.trim_end_matches('/')),
+            None => GITLAB_HOST.to_string(),
+        };
+        format!(
+            "{}/api/v4/projects/{}/releases",
+            host,
+            self.gitlab.project.trim_matches('/').replace('/', "%2F")
+        )
+    }
+
+    async fn load_page(&self, page: u32) -> Result<Vec<RemoteRelease>> {
+        let releases: Vec<GitlabRelease> = get_json(
+            &format!("{}?page={}&per_page={}", self.api_url(), page, PER_PAGE),
+            HeaderMap::new(),
+        )
+        .await
+        .map_err(|e| InstallError::Fetch("releases", e))?;
+        Ok(releases.into_iter().map(|r| r.into()).collect())
+    }
+}
+
+impl ReleaseProvider for GitlabProvider {
+    async fn list_releases(&self) -> Result<Vec<RemoteRelease>> {
+        collect_pages(|page| self.load_page(page)).await
+    }
+
+    async fn releases_page(&self, page: u32) -> Result<Vec<RemoteRelease>> {
+        self.load_page(page).await
+    }
+
+    async fn find_release(&self, version: &str) -> Result<Option<RemoteRelease>> {
+        find_in_pages(version, &self.tag_format, |page| self.load_page(page)).await
+    }
+
+    async fn assets(&self, release: &RemoteRelease) -> Result<Vec<AppAsset>> {
+        Ok(release.assets.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::action::installer::provider::test::{check_pages, mock_pages};
+    use mockito::Server;
+
+    const PAGE_1: &str = r#"[
+        {"tag_name": "v1.3.0", "upcoming_release": true, "assets": {"sources": [], "links": []}},
+        {"tag_name": "v1.2.0", "released_at": "2024-01-01T00:00:00Z",
+         "assets": {
+            "sources": [{"format": "tar.gz", "url": "https://gitlab.local/group/tool/-/archive/v1.2.0.tar.gz"},
+                        {"format": "zip", "url": "https://gitlab.local/group/tool/-/archive/v1.2.0.zip"}],
+            "links": [{"name": "tool-x86_64-linux-gnu.tar.gz",
+                       "url": "https://gitlab.local/uploads/tool-x86_64-linux-gnu.tar.gz",
+                       "direct_asset_url": "https://gitlab.local/group/tool/-/releases/v1.2.0/downloads/tool.tar.gz"}]}}
+    ]"#;
+    const PAGE_2: &str = r#"[
+        {"tag_name": "v1.0.0", "assets": {"links": [{"name": "tool.zip", "url": "https://gitlab.local/tool.zip"}]}}
+    ]"#;
+
+    fn provider(host: Option<String>) -> GitlabProvider {
+        GitlabProvider::new(
+            Gitlab {
+                project: "group/tool".to_string(),
+                host,
+            },
+            TagFormat::new(None, "tool"),
+        )
+    }
+
+    #[test]
+    fn test_api_url() {
+        assert_eq!(
+            "https://gitlab.com/api/v4/projects/group%2Ftool/releases",
+            provider(None).api_url()
+        );
+        assert_eq!(
+            "https://gitlab.example.com/api/v4/projects/group%2Ftool/releases",
+            provider(Some("gitlab.example.com/".to_string())).api_url()
+        );
+        assert_eq!(
+            "http://gitlab.local/api/v4/projects/group%2Ftool/releases",
+            provider(Some("http://gitlab.local".to_string())).api_url()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pages() {
+        let mut server = Server::new_async().await;
+        mock_pages(
+            &mut server,
+            "/api/v4/projects/group%2Ftool/releases",
+            &[PAGE_1, PAGE_2],
+        )
+        .await;
+
+        let (release, old) = check_pages(&provider(Some(server.url()))).await;
+        let source = release.source.unwrap();
+        assert_eq!(
+            "https://gitlab.local/group/tool/-/archive/v1.2.0.zip",
+            source.url
+        );
+        assert_eq!("v1.2.0.zip", source.file_name);
+        assert_eq!(1, release.assets.len());
+        assert_eq!("tool-x86_64-linux-gnu.tar.gz", release.assets[0].name);
+        assert_eq!(
+            "https://gitlab.local/group/tool/-/releases/v1.2.0/downloads/tool.tar.gz",
+            release.assets[0].download_url
+        );
+        // a link without a direct url
+        assert_eq!("https://gitlab.local/tool.zip", old.assets[0].download_url);
+        assert!(old.source.is_none());
+    }
+}
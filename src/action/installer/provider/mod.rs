@@ -0,0 +1,375 @@
+mod crates_io;
+mod gitea;
+mod github;
+mod gitlab;
+mod url_template;
+
+use super::execute::LATEST_VERSION;
+use super::install::{AppAsset, AppSource, InstallError, Result};
+use crate::tool::kaleido::Package;
+use log::debug;
+#[cfg(test)]
+use mockall::automock;
+use std::future::Future;
+
+pub use crates_io::CratesIoProvider;
+pub use gitea::GiteaProvider;
+pub use github::{get_github_json, GithubProvider, GithubRelease};
+pub use gitlab::GitlabProvider;
+pub use url_template::UrlTemplateProvider;
+
+/// a release returned by a provider, before assets are filtered for current platform.
+#[derive(Clone, Debug, Default)]
+pub struct RemoteRelease {
+    pub tag_name: String,
+    pub draft: bool,
+    pub prerelease: bool,
+    /// publish time in RFC 3339, e.g. '2024-01-01T00:00:00Z'.
+    pub published_at: Option<String>,
+    /// the source code archive.
+    pub source: Option<AppSource>,
+    pub assets: Vec<AppAsset>,
+}
+
+/// a place where releases of a package are published.
+#[cfg_attr(test, automock)]
+pub trait ReleaseProvider {
+    /// list all releases, the latest one is the first.
+    async fn list_releases(&self) -> Result<Vec<RemoteRelease>>;
+
+    /// one page of releases from 1, the latest one is the first, an empty page means there are no more releases.
+    /// providers without pages return all releases in the first page.
+    async fn releases_page(&self, page: u32) -> Result<Vec<RemoteRelease>> {
+        if page == 1 {
+            self.list_releases().await
+        } else {
+            Ok(vec![])
+        }
+    }
+
+    /// find the release of the given version, `LATEST_VERSION` means the latest stable release.
+    async fn find_release(&self, version: &str) -> Result<Option<RemoteRelease>>;
+
+    /// all downloadable files of the release.
+    async fn assets(&self, release: &RemoteRelease) -> Result<Vec<AppAsset>>;
+
+    /// true if the assets are for current platform already, so they don't need to be filtered.
+    fn is_platform_specific(&self) -> bool {
+        false
+    }
+}
+
+/// the provider configured in a package.
+pub enum PackageProvider {
+    Github(GithubProvider),
+    Gitlab(GitlabProvider),
+    Gitea(GiteaProvider),
+    UrlTemplate(UrlTemplateProvider),
+    CratesIo(CratesIoProvider),
+}
+
+impl PackageProvider {
+    pub fn from_package(package: &Package, rust_abi: &str) -> Result<Self> {
+        let tag_format = TagFormat::from_package(package);
+        if let Some(gt) = package.github.as_ref() {
+            Ok(PackageProvider::Github(GithubProvider::new(
+                gt.clone(),
+                tag_format,
+            )))
+        } else if let Some(gl) = package.gitlab.as_ref() {
+            Ok(PackageProvider::Gitlab(GitlabProvider::new(
+                gl.clone(),
+                tag_format,
+            )))
+        } else if let Some(gt) = package.gitea.as_ref() {
+            Ok(PackageProvider::Gitea(GiteaProvider::new(
+                gt.clone(),
+                tag_format,
+            )))
+        } else if let Some(ut) = package.url_template.as_ref() {
+            Ok(PackageProvider::UrlTemplate(UrlTemplateProvider::new(
+                ut.clone(),
+                rust_abi,
+            )))
+        } else if let Some(crates_io) = package.crates_io.as_ref() {
+            Ok(PackageProvider::CratesIo(CratesIoProvider::new(
+                crates_io.clone(),
+                &package.name,
+            )))
+        } else {
+            Err(InstallError::GeneralStr(
+                "no release source is configured for the package",
+            ))
+        }
+    }
+}
+
+impl ReleaseProvider for PackageProvider {
+    async fn list_releases(&self) -> Result<Vec<RemoteRelease>> {
+        match self {
+            PackageProvider::Github(p) => p.list_releases().await,
+            PackageProvider::Gitlab(p) => p.list_releases().await,
+            PackageProvider::Gitea(p) => p.list_releases().await,
+            PackageProvider::UrlTemplate(p) => p.list_releases().await,
+            PackageProvider::CratesIo(p) => p.list_releases().await,
+        }
+    }
+
+    async fn releases_page(&self, page: u32) -> Result<Vec<RemoteRelease>> {
+        match self {
+            PackageProvider::Github(p) => p.releases_page(page).await,
+            PackageProvider::Gitlab(p) => p.releases_page(page).await,
+            PackageProvider::Gitea(p) => p.releases_page(page).await,
+            PackageProvider::UrlTemplate(p) => p.releases_page(page).await,
+            PackageProvider::CratesIo(p) => p.releases_page(page).await,
+        }
+    }
+
+    async fn find_release(&self, version: &str) -> Result<Option<RemoteRelease>> {
+        match self {
+            PackageProvider::Github(p) => p.find_release(version).await,
+            PackageProvider::Gitlab(p) => p.find_release(version).await,
+            PackageProvider::Gitea(p) => p.find_release(version).await,
+            PackageProvider::UrlTemplate(p) => p.find_release(version).await,
+            PackageProvider::CratesIo(p) => p.find_release(version).await,
+        }
+    }
+
+    async fn assets(&self, release: &RemoteRelease) -> Result<Vec<AppAsset>> {
+        match self {
+            PackageProvider::Github(p) => p.assets(release).await,
+            PackageProvider::Gitlab(p) => p.assets(release).await,
+            PackageProvider::Gitea(p) => p.assets(release).await,
+            PackageProvider::UrlTemplate(p) => p.assets(release).await,
+            PackageProvider::CratesIo(p) => p.assets(release).await,
+        }
+    }
+
+    fn is_platform_specific(&self) -> bool {
+        match self {
+            PackageProvider::Github(p) => p.is_platform_specific(),
+            PackageProvider::Gitlab(p) => p.is_platform_specific(),
+            PackageProvider::Gitea(p) => p.is_platform_specific(),
+            PackageProvider::UrlTemplate(p) => p.is_platform_specific(),
+            PackageProvider::CratesIo(p) => p.is_platform_specific(),
+        }
+    }
+}
+
+/// how a version is written in tag names, e.g. 'release-{version}' or '{name}-v{version}'.
+/// without a format, the tag name is the version, or `v` + version.
+#[derive(Clone, Debug)]
+pub struct TagFormat {
+    format: Option<String>,
+}
+
+impl TagFormat {
+    pub fn new(format: Option<&str>, package_name: &str) -> Self {
+        TagFormat {
+            format: format.map(|f| f.replace("{name}", package_name)),
+        }
+    }
+
+    pub fn from_package(package: &Package) -> Self {
+        TagFormat::new(package.tag_format.as_deref(), &package.name)
+    }
+
+    /// all possible tag names for the given version.
+    pub fn tags(&self, version: &str) -> Vec<String> {
+        let version = normalize_version(version);
+        match &self.format {
+            Some(f) => vec![f.replace("{version}", version)],
+            None => vec![version.to_string(), format!("v{}", version)],
+        }
+    }
+
+    /// the version in the tag name, None if the tag doesn't match the format.
+    pub fn version_of(&self, tag: &str) -> Option<String> {
+        let version = match &self.format {
+            Some(f) => {
+                let (prefix, suffix) = f.split_once("{version}")?;
+                let version = tag.strip_prefix(prefix)?.strip_suffix(suffix)?;
+                if version.is_empty() {
+                    return None;
+                }
+                version
+            }
+            None => tag,
+        };
+        Some(normalize_version(version).to_string())
+    }
+}
+
+/// remove the `v` before a version number, so 'v1.2.3' and '1.2.3' are the same.
+pub fn normalize_version(version: &str) -> &str {
+    match version.strip_prefix('v') {
+        Some(v) if v.starts_with(|c: char| c.is_ascii_digit()) => v,
+        _ => version,
+    }
+}
+
+/// load pages one by one until a release matches the given version.
+async fn find_in_pages<F, Fut>(
+    version: &str,
+    tag_format: &TagFormat,
+    load_page: F,
+) -> Result<Option<RemoteRelease>>
+where
+    F: Fn(u32) -> Fut,
+    Fut: Future<Output = Result<Vec<RemoteRelease>>>,
+{
+    let versions = tag_format.tags(version);
+    let mut page: u32 = 1;
+    loop {
+        let releases = load_page(page).await?;
+        if releases.is_empty() {
+            return Ok(None);
+        }
+        // filter by draft and prerelease
+        match releases.into_iter().find(|r| {
+            if version == LATEST_VERSION {
+                // other crates in the same repository have their own tags
+                !(r.draft || r.prerelease) && tag_format.version_of(&r.tag_name).is_some()
+            } else {
+                versions.contains(&r.tag_name)
+            }
+        }) {
+            Some(r) => return Ok(Some(r)),
+            None => {
+                page += 1;
+                debug!("load next page releases: {}", page);
+            }
+        }
+    }
+}
+
+/// load all pages.
+async fn collect_pages<F, Fut>(load_page: F) -> Result<Vec<RemoteRelease>>
+where
+    F: Fn(u32) -> Fut,
+    Fut: Future<Output = Result<Vec<RemoteRelease>>>,
+{
+    let mut all = vec![];
+    let mut page: u32 = 1;
+    loop {
+        let releases = load_page(page).await?;
+        if releases.is_empty() {
+            return Ok(all);
+        }
+        all.extend(releases);
+        page += 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mockito::{Matcher, Mock, Server};
+
+    /// serve release pages of a provider at the path by the `page` query, the page after them is empty.
+    pub(super) async fn mock_pages(server: &mut Server, path: &str, pages: &[&str]) -> Vec<Mock> {
+        let mut mocks = vec![];
+        let empty = ["[]"];
+        for (i, body) in pages.iter().chain(empty.iter()).enumerate() {
+            mocks.push(
+                server
+                    .mock("GET", path)
+                    .match_query(Matcher::UrlEncoded("page".into(), (i + 1).to_string()))
+                    .with_header("content-type", "application/json")
+                    .with_body(*body)
+                    .create_async()
+                    .await,
+            );
+        }
+        mocks
+    }
+
+    /// check paging of a provider serving its pages with `mock_pages`: the first page has a draft 'v1.3.0'
+    /// and the latest stable 'v1.2.0', the second one has 'v1.0.0', other releases must be prereleases.
+    /// the latest release and 'v1.0.0' are returned for checks of the provider.
+    pub(super) async fn check_pages<P: ReleaseProvider>(
+        provider: &P,
+    ) -> (RemoteRelease, RemoteRelease) {
+        let latest = provider
+            .find_release(LATEST_VERSION)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!("v1.2.0", latest.tag_name);
+        let old = provider.find_release("1.0.0").await.unwrap().unwrap();
+        assert_eq!("v1.0.0", old.tag_name);
+        assert!(provider.find_release("0.9.0").await.unwrap().is_none());
+
+        let releases = provider.list_releases().await.unwrap();
+        let tags: Vec<_> = releases
+            .iter()
+            .filter(|r| !r.prerelease)
+            .map(|r| r.tag_name.as_str())
+            .collect();
+        assert_eq!(vec!["v1.3.0", "v1.2.0", "v1.0.0"], tags);
+        assert!(
+            releases
+                .iter()
+                .find(|r| r.tag_name == "v1.3.0")
+                .unwrap()
+                .draft
+        );
+        (latest, old)
+    }
+
+    fn release(tag_name: &str) -> RemoteRelease {
+        RemoteRelease {
+            tag_name: tag_name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_tag_format() {
+        let default = TagFormat::new(None, "tool");
+        assert_eq!(vec!["1.2.3", "v1.2.3"], default.tags("v1.2.3"));
+        assert_eq!(Some("1.2.3".to_string()), default.version_of("v1.2.3"));
+        assert_eq!(Some("1.2.3".to_string()), default.version_of("1.2.3"));
+        assert_eq!(Some("version".to_string()), default.version_of("version"));
+
+        let release = TagFormat::new(Some("release-{version}"), "tool");
+        assert_eq!(vec!["release-1.2.3"], release.tags("1.2.3"));
+        assert_eq!(
+            Some("1.2.3".to_string()),
+            release.version_of("release-1.2.3")
+        );
+        assert_eq!(None, release.version_of("1.2.3"));
+
+        let monorepo = TagFormat::new(Some("{name}-v{version}"), "tool");
+        assert_eq!(vec!["tool-v1.2.3"], monorepo.tags("v1.2.3"));
+        assert_eq!(
+            Some("1.2.3".to_string()),
+            monorepo.version_of("tool-v1.2.3")
+        );
+        assert_eq!(None, monorepo.version_of("tool-core-v1.2.3"));
+        assert_eq!(None, monorepo.version_of("tool-v"));
+    }
+
+    #[tokio::test]
+    async fn test_find_in_pages_with_format() {
+        let pages = [
+            vec![release("tool-core-v0.5.0"), release("tool-v1.1.0")],
+            vec![release("tool-v1.0.0")],
+            vec![],
+        ];
+        let load_page = |page: u32| {
+            let releases = pages[page as usize - 1].clone();
+            async move { Ok(releases) }
+        };
+        let format = TagFormat::new(Some("{name}-v{version}"), "tool");
+
+        let latest = find_in_pages(LATEST_VERSION, &format, load_page)
+            .await
+            .unwrap();
+        assert_eq!("tool-v1.1.0", latest.unwrap().tag_name);
+        let old = find_in_pages("1.0.0", &format, load_page).await.unwrap();
+        assert_eq!("tool-v1.0.0", old.unwrap().tag_name);
+        let missing = find_in_pages("0.5.0", &format, load_page).await.unwrap();
+        assert!(missing.is_none());
+    }
+}
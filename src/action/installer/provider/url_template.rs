@@ -0,0 +1,190 @@
+use super::{ReleaseProvider, RemoteRelease};
+use crate::action::installer::execute::LATEST_VERSION;
+use crate::action::installer::install::{
+    to_github_arch, to_github_os, AppAsset, InstallError, Result,
+};
+use crate::tool::{http::get_text, kaleido::UrlTemplate};
+use log::debug;
+use std::env::consts::{ARCH, OS};
+
+const DEFAULT_EXT: &str = "tar.gz";
+
+pub struct UrlTemplateProvider {
+    template: UrlTemplate,
+    rust_abi: String,
+}
+
+impl UrlTemplateProvider {
+    pub fn new(template: UrlTemplate, rust_abi: &str) -> Self {
+        UrlTemplateProvider {
+            template,
+            rust_abi: rust_abi.to_string(),
+        }
+    }
+
+    async fn latest_version(&self) -> Result<String> {
+        if let Some(latest_url) = self.template.latest_url.as_ref() {
+            let version = get_text(latest_url).await?.trim().to_string();
+            if version.is_empty() {
+                return Err(InstallError::General(format!(
+                    "no version is returned from {}",
+                    latest_url
+                )));
+            }
+            return Ok(version);
+        }
+        match self.template.versions.as_ref().and_then(|v| v.first()) {
+            Some(v) => Ok(v.clone()),
+            None => Err(InstallError::GeneralStr(
+                "either 'versions' or 'latest_url' is required to find the latest version",
+            )),
+        }
+    }
+}
+
+fn release(version: &str) -> RemoteRelease {
+    RemoteRelease {
+        tag_name: version.to_string(),
+        ..Default::default()
+    }
+}
+
+impl ReleaseProvider for UrlTemplateProvider {
+    async fn list_releases(&self) -> Result<Vec<RemoteRelease>> {
+        match self.template.versions.as_ref() {
+            Some(versions) => Ok(versions.iter().map(|v| release(v)).collect()),
+            None => Ok(vec![release(&self.latest_version().await?)]),
+        }
+    }
+
+    async fn find_release(&self, version: &str) -> Result<Option<RemoteRelease>> {
+        if version == LATEST_VERSION {
+            return Ok(Some(release(&self.latest_version().await?)));
+        }
+        match self.template.versions.as_ref() {
+            Some(versions) if !versions.iter().any(|v| v == version) => {
+                debug!("version {} is not in the version list", version);
+                Ok(None)
+            }
+            _ => Ok(Some(release(version))),
+        }
+    }
+
+    async fn assets(&self, release: &RemoteRelease) -> Result<Vec<AppAsset>> {
+        let url = render(&self.template, &release.tag_name, OS, ARCH, &self.rust_abi);
+        let name = match url.rsplit('/').next() {
+            Some(n) if !n.is_empty() => n.to_string(),
+            _ => {
+                return Err(InstallError::General(format!(
+                    "invalid download url: {}",
+                    url
+                )))
+            }
+        };
+        Ok(vec![AppAsset {
+            name,
+            download_url: url,
+            bin_path: None,
+        }])
+    }
+
+    /// the url is built for current platform already.
+    fn is_platform_specific(&self) -> bool {
+        true
+    }
+}
+
+fn render(ut: &UrlTemplate, version: &str, os: &str, arch: &str, rust_abi: &str) -> String {
+    let ext = if os == "windows" {
+        ut.windows_ext.as_ref().or(ut.ext.as_ref())
+    } else {
+        ut.ext.as_ref()
+    };
+    ut.url
+        .replace("{version}", version)
+        .replace("{os}", to_github_os(os))
+        .replace("{arch}", to_github_arch(arch))
+        .replace("{abi}", rust_abi)
+        .replace("{ext}", ext.map(|e| e.as_str()).unwrap_or(DEFAULT_EXT))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mockito::Server;
+
+    fn template(url: &str) -> UrlTemplate {
+        UrlTemplate {
+            url: url.to_string(),
+            ext: None,
+            windows_ext: Some("zip".to_string()),
+            versions: None,
+            latest_url: None,
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let ut = template("https://example.com/dl/{version}/tool-{os}-{arch}-{abi}.{ext}");
+        assert_eq!(
+            "https://example.com/dl/1.2.3/tool-linux-x86_64-musl.tar.gz",
+            render(&ut, "1.2.3", "linux", "x86_64", "musl")
+        );
+        assert_eq!(
+            "https://example.com/dl/1.2.3/tool-darwin-aarch64-gnu.tar.gz",
+            render(&ut, "1.2.3", "macos", "aarch64", "gnu")
+        );
+        assert_eq!(
+            "https://example.com/dl/1.2.3/tool-windows-x86_64-msvc.zip",
+            render(&ut, "1.2.3", "windows", "x86_64", "msvc")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_version_list() {
+        let mut ut = template("https://example.com/dl/{version}/tool.{ext}");
+        ut.versions = Some(vec!["2.0.0".to_string(), "1.0.0".to_string()]);
+        let provider = UrlTemplateProvider::new(ut, "gnu");
+
+        let latest = provider
+            .find_release(LATEST_VERSION)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!("2.0.0", latest.tag_name);
+        let assets = provider.assets(&latest).await.unwrap();
+        assert_eq!("tool.tar.gz", assets[0].name);
+
+        let old = provider.find_release("1.0.0").await.unwrap();
+        assert!(old.is_some());
+        let missing = provider.find_release("3.0.0").await.unwrap();
+        assert!(missing.is_none());
+        assert_eq!(2, provider.list_releases().await.unwrap().len());
+    }
+
+    #[tokio::test]
+    async fn test_latest_url() {
+        let mut server = Server::new_async().await;
+        server
+            .mock("GET", "/latest.txt")
+            .with_body("v1.4.0\n")
+            .create_async()
+            .await;
+        let mut ut = template("https://example.com/dl/{version}/tool.{ext}");
+        ut.latest_url = Some(format!("{}/latest.txt", server.url()));
+
+        let provider = UrlTemplateProvider::new(ut, "gnu");
+
+        let latest = provider
+            .find_release(LATEST_VERSION)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!("v1.4.0", latest.tag_name);
+        let assets = provider.assets(&latest).await.unwrap();
+        assert_eq!(
+            "https://example.com/dl/v1.4.0/tool.tar.gz",
+            assets[0].download_url
+        );
+    }
+}
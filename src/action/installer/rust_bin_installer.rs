@@ -1,208 +1,827 @@
 use super::{
-    execute::{InstallRequest, LATEST_VERSION},
-    install::{find_assets, AppRelease, InstallError, Installer, InstallerContext},
+    binstall::SourceLoader,
+    execute::{InstallRequest, InstallStatus, LATEST_VERSION},
+    install::{find_assets, AppAsset, AppRelease, AppSource, InstallError, InstallerContext},
+    provider::{normalize_version, CratesIoProvider, ReleaseProvider},
     rust_src_installer::RustSrcInstaller,
+    transaction::{hidden_sibling, verify_bin, Transaction},
 };
 use crate::tool::{
-    fs::{decompress, get_file_type, make_link, remove_link, AppDir, FileType},
+    cache::{download_cached, DownloadCache},
+    fs::{decompress, get_file_type, sha256_file, AppDir, FileType},
     global_input::GlobalInput,
-    http::download_with_progress,
     kaleido::Package,
+    state::{unix_now, BuildKind, PackageReceipts, Receipt},
 };
 use colored::Colorize;
+use indicatif::ProgressBar;
 use log::{debug, error};
-use std::fs::{copy, create_dir_all, remove_dir_all, remove_file};
+use std::collections::BTreeSet;
+use std::fs::{copy, read_dir, read_link};
+use std::path::{Path, PathBuf};
 use std::{env::consts::EXE_EXTENSION, io::Write};
+use tempfile::TempDir;
 
 pub struct RustBinInstaller<'a, 'b> {
     global_input: &'b mut GlobalInput<'a>,
 }
 
-impl<'a, 'b> RustBinInstaller<'a, 'b> {
-    pub fn new(global_input: &'b mut GlobalInput<'a>) -> Self {
-        RustBinInstaller { global_input }
-    }
+/// the first step of an install, it could run with other installs at the same time.
+pub enum Prepared {
+    /// downloaded and staged, but not linked yet
+    Staged(Box<StagedPackage>),
+    Unchanged(String),
+    /// no released binary for current platform, it could be built from source code
+    Source(AppRelease),
+    Failed,
+}
 
-    async fn install_package(
-        &self,
-        release: &AppRelease,
-        package: &Package,
-        alias: &Option<String>,
-        app_dir: &AppDir,
-        force_install: bool,
-    ) -> std::result::Result<(), InstallError> {
-        let asset = &release.assets[0];
-        let tmp_dir = app_dir.get_home_dir().join("tmp");
-        let to_file = tmp_dir.join(&asset.name);
-        if to_file.exists() && to_file.is_file() {
-            remove_file(&to_file)?;
-            // if let Err(e) = remove_file(&to_file) {
-            //     error!("failed to delete file({:?}): {}", to_file, e);
-            //     return;
-            // }
+/// a downloaded package in a staging directory, it's removed if the package is not activated.
+pub struct StagedPackage {
+    release: AppRelease,
+    staging_dir: PathBuf,
+    package_dir: PathBuf,
+    package_bin_file: PathBuf,
+    legacy_dir: PathBuf,
+    sha256: Option<String>,
+    transaction: Transaction,
+    _tmp: TempDir,
+}
+
+/// find the release, then download and stage the binary, nothing is linked.
+pub async fn prepare<P: ReleaseProvider>(
+    provider: &P,
+    package: &Package,
+    request: &InstallRequest,
+    app_dir: &AppDir,
+    context: &InstallerContext,
+    pb: &ProgressBar,
+) -> Prepared {
+    let final_version = match &request.version {
+        Some(v) => v,
+        None => LATEST_VERSION,
+    };
+    let tmp = match app_dir.create_tmp_dir(&package.name) {
+        Ok(t) => t,
+        Err(e) => {
+            error!("failed to create temporary directory: {}", e);
+            pb.abandon_with_message(format!("failed to create temporary directory: {}", e));
+            return Prepared::Failed;
+        }
+    };
+    let loader = SourceLoader {
+        app_dir,
+        tmp_dir: tmp.path(),
+        metadata: &context.binstall_sources,
+        pb,
+    };
+    let mut app_release = match find_assets(
+        provider,
+        package,
+        final_version,
+        &package.remote_file_prefix,
+        &context.rust_abi,
+        request.prerelease,
+        &loader,
+    )
+    .await
+    {
+        Ok(r) => r,
+        // only a release which cannot be loaded is replaced by the cached one, not a wrong config or version
+        Err(e) => match cached_release(package, request, app_dir).filter(|_| e.is_network()) {
+            Some(r) => {
+                pb.suspend(|| {
+                    println!(
+                        "{}",
+                        format!(
+                            "failed to find the release of {}: {}, install the cached release {} instead",
+                            package.name, e, r.version
+                        )
+                        .yellow()
+                    )
+                });
+                r
+            }
+            None => {
+                error!("failed to get assets: {}", e);
+                pb.abandon_with_message(format!("failed to get assets: {}", e));
+                return Prepared::Failed;
+            }
+        },
+    };
+
+    if app_release.assets.is_empty() {
+        if let Some(source) = find_crate_source(package, &app_release).await {
+            app_release.source = Some(source);
         }
+    }
 
-        let package_dir = app_dir
-            .get_packages_dir()
-            .join(&package.name)
-            .join(&release.version);
-
-        let mut package_bin_file = package_dir.join(&package.bin_name);
-        package_bin_file.set_extension(EXE_EXTENSION);
-
-        if package_dir.exists() && package_dir.is_dir() {
-            if force_install {
-                remove_dir_all(&package_dir)?;
-                // if let Err(e) = remove_dir_all(&package_dir) {
-                //     error!("failed to delete dir({:?}): {}", package_dir, e);
-                //     return;
-                // }
-            } else if package_bin_file.exists() && package_bin_file.is_file() {
-                println!(
-                    "the package {} with version {} is already installed, skip it",
-                    package.name, release.version
-                );
-                return Ok(());
+    if !app_release.assets.is_empty() {
+        match stage_package(app_release, package, app_dir, tmp, context.force, pb).await {
+            Err(e) => {
+                pb.suspend(|| {
+                    println!(
+                        "{}",
+                        format!("failed to install {}: {}", package.name, e).red()
+                    )
+                });
+                Prepared::Failed
             }
+            Ok(Err(version)) => Prepared::Unchanged(version),
+            Ok(Ok(staged)) => Prepared::Staged(Box::new(staged)),
         }
-        download_with_progress(to_file, asset.download_url.as_str()).await?;
-        // if let Err(e) = download_with_progress(to_file, asset.download_url.as_str()).await {
-        //     error!("failed to download file: {}", e);
-        //     return;
-        // }
-
-        create_dir_all(&package_dir)?;
-        // if let Err(e) = create_dir_all(&package_dir) {
-        //     error!("failed to create dir({:?}): {}", package_dir, e);
-        //     return;
-        // }
-
-        let to_file = tmp_dir.join(&asset.name);
-        let exe_ext = format!(".{}", EXE_EXTENSION);
-        debug!(
-            "{}, {}, {}",
-            asset.name,
-            exe_ext,
-            asset.name.ends_with(&exe_ext)
-        );
-        if !EXE_EXTENSION.is_empty() && asset.name.ends_with(&exe_ext) {
-            copy(&to_file, &package_bin_file)?;
-        } else {
-            match get_file_type(&asset.name) {
-                FileType::Unknown => {
-                    // error!("unsupported file type: {}", asset.name);
-                    return Err(InstallError::GeneralStr("unsupported file type"));
-                }
-                FileType::Compression => match decompress(&to_file, &tmp_dir) {
-                    Ok(folder) => {
-                        debug!("decompress output: {:?}", folder);
-                        let mut bin_file = match folder {
-                            Some(f) => tmp_dir.join(f),
-                            None => tmp_dir.clone(),
-                        };
-                        if let Some(f) = &package.bin_path {
-                            bin_file = bin_file.join(f);
+    } else if app_release.source.is_some() {
+        pb.finish_and_clear();
+        Prepared::Source(app_release)
+    } else {
+        pb.suspend(|| println!("{}", "cannot find appropriate package to install".red()));
+        pb.finish_and_clear();
+        Prepared::Failed
+    }
+}
+
+/// the release of the requested version, or the current one, from its receipt if its asset is still cached,
+/// so a package could be installed again when the release cannot be found, e.g. offline.
+fn cached_release(
+    package: &Package,
+    request: &InstallRequest,
+    app_dir: &AppDir,
+) -> Option<AppRelease> {
+    let receipts = PackageReceipts::load(app_dir, &package.name).ok()?;
+    let receipt = match &request.version {
+        Some(v) => receipts
+            .versions
+            .iter()
+            .find(|r| normalize_version(&r.version) == normalize_version(v))?,
+        None => receipts.current_receipt()?,
+    };
+    if receipt.build != Some(BuildKind::Binary) {
+        return None;
+    }
+    let url = receipt.source_url.as_ref()?;
+    let name = receipt.asset_name.as_ref()?;
+    DownloadCache::open(app_dir).lookup_url(url).ok()??;
+    Some(AppRelease {
+        version: receipt.version.clone(),
+        source: None,
+        assets: vec![AppAsset {
+            name: name.clone(),
+            download_url: url.clone(),
+            bin_path: None,
+        }],
+        identity: None,
+    })
+}
+
+/// download the asset into a staging directory, or the version if it's already installed.
+async fn stage_package(
+    release: AppRelease,
+    package: &Package,
+    app_dir: &AppDir,
+    tmp: TempDir,
+    force_install: bool,
+    pb: &ProgressBar,
+) -> std::result::Result<std::result::Result<StagedPackage, String>, InstallError> {
+    let asset = &release.assets[0];
+    let tmp_dir = tmp.path().to_path_buf();
+    let to_file = tmp_dir.join(&asset.name);
+
+    let package_dir = app_dir
+        .get_packages_dir()
+        .join(&package.name)
+        .join(&release.version);
+
+    let mut package_bin_file = package_dir.join(&package.bin_name);
+    package_bin_file.set_extension(EXE_EXTENSION);
+
+    // the directory was named by the tag before, e.g. 'v1.0.0'
+    let legacy_dir = app_dir
+        .get_packages_dir()
+        .join(&package.name)
+        .join(format!("v{}", release.version));
+    if !force_install
+        && (legacy_dir.join(package_bin_file.file_name().unwrap_or_default()).is_file()
+            || package_bin_file.is_file())
+    {
+        pb.suspend(|| {
+            println!(
+                "the package {} with version {} is already installed, skip it",
+                package.name, release.version
+            )
+        });
+        pb.finish_and_clear();
+        return Ok(Err(release.version));
+    }
+    download_cached(
+        app_dir,
+        &to_file,
+        asset.download_url.as_str(),
+        release.identity.as_deref(),
+        pb,
+    )
+    .await?;
+
+    // nothing in packages or bin directories is changed until the new version is ready
+    let mut transaction = Transaction::new();
+    let staging_dir = hidden_sibling(&package_dir, "staging");
+    transaction.create_dir(&staging_dir)?;
+    let mut staged_bin_file = staging_dir.join(&package.bin_name);
+    staged_bin_file.set_extension(EXE_EXTENSION);
+
+    // decompressing and hashing are slow, they don't block other downloads
+    let (asset, bin_package) = (asset.clone(), package.clone());
+    let sha256 = tokio::task::spawn_blocking(move || {
+        unpack(&asset, &bin_package, &to_file, &tmp_dir, &staged_bin_file)
+    })
+    .await
+    .map_err(|e| InstallError::General(format!("failed to unpack: {}", e)))??;
+
+    Ok(Ok(StagedPackage {
+        release,
+        staging_dir,
+        package_dir,
+        package_bin_file,
+        legacy_dir,
+        sha256,
+        transaction,
+        _tmp: tmp,
+    }))
+}
+
+/// copy the executable file from the downloaded file to the staging directory, the sha256 of the downloaded file is returned.
+fn unpack(
+    asset: &AppAsset,
+    package: &Package,
+    to_file: &Path,
+    tmp_dir: &Path,
+    staged_bin_file: &Path,
+) -> std::result::Result<Option<String>, InstallError> {
+    let exe_ext = format!(".{}", EXE_EXTENSION);
+    debug!(
+        "{}, {}, {}",
+        asset.name,
+        exe_ext,
+        asset.name.ends_with(&exe_ext)
+    );
+    if !EXE_EXTENSION.is_empty() && asset.name.ends_with(&exe_ext) {
+        copy(to_file, staged_bin_file)?;
+    } else {
+        match get_file_type(&asset.name) {
+            FileType::Unknown => {
+                // error!("unsupported file type: {}", asset.name);
+                return Err(InstallError::GeneralStr("unsupported file type"));
+            }
+            FileType::Compression => match decompress(to_file, tmp_dir) {
+                Ok(folder) => {
+                    debug!("decompress output: {:?}", folder);
+                    let bin_file = match &asset.bin_path {
+                        Some(p) => {
+                            let bin_file = tmp_dir.join(p);
+                            // like cargo-binstall, a binary at the root of the archive is also accepted
+                            match bin_file.file_name() {
+                                Some(f) if !bin_file.is_file() => tmp_dir.join(f),
+                                _ => bin_file,
+                            }
                         }
-                        bin_file = bin_file.join(&package.bin_name);
-                        bin_file.set_extension(EXE_EXTENSION);
-                        copy(&bin_file, &package_bin_file)?;
-                    }
-                    Err(e) => {
-                        return Err(InstallError::ToolFs(e));
-                    }
-                },
-                FileType::Plain => {
-                    copy(&to_file, &package_bin_file)?;
+                        None => {
+                            let mut bin_file = match folder {
+                                Some(f) => tmp_dir.join(f),
+                                None => tmp_dir.to_path_buf(),
+                            };
+                            if let Some(f) = &package.bin_path {
+                                bin_file = bin_file.join(f);
+                            }
+                            bin_file = bin_file.join(&package.bin_name);
+                            bin_file.set_extension(EXE_EXTENSION);
+                            bin_file
+                        }
+                    };
+                    copy(&bin_file, staged_bin_file)?;
+                }
+                Err(e) => {
+                    return Err(InstallError::ToolFs(e));
                 }
+            },
+            FileType::Plain => {
+                copy(to_file, staged_bin_file)?;
             }
         }
+    }
+    verify_bin(staged_bin_file)?;
+    Ok(sha256_file(to_file).ok())
+}
+
+/// move the staged package into packages directory and link it, it's rolled back on any error.
+fn activate(
+    staged: StagedPackage,
+    package: &Package,
+    alias: &Option<String>,
+    app_dir: &AppDir,
+) -> std::result::Result<String, InstallError> {
+    let StagedPackage {
+        release,
+        staging_dir,
+        package_dir,
+        package_bin_file,
+        legacy_dir,
+        sha256,
+        mut transaction,
+        _tmp,
+    } = staged;
+    let asset = &release.assets[0];
 
-        let mut sys_bin_file = app_dir.get_bin_dir().join(&package.bin_name);
+    transaction.replace_dir(&staging_dir, &package_dir)?;
+    let mut sys_bin_file = app_dir.get_bin_dir().join(&package.bin_name);
+    sys_bin_file.set_extension(EXE_EXTENSION);
+    transaction.swap_link(&sys_bin_file, &package_bin_file)?;
+
+    let mut aliases: Vec<String> = alias.iter().cloned().collect();
+    if let Some(alias_str) = alias {
+        let mut sys_bin_file = app_dir.get_alias_dir().join(alias_str);
         sys_bin_file.set_extension(EXE_EXTENSION);
-        if sys_bin_file.exists() && sys_bin_file.is_file() {
-            remove_link(&sys_bin_file)?;
-        }
-        make_link(&sys_bin_file, &package_bin_file)?;
+        transaction.swap_link(&sys_bin_file, &package_bin_file)?;
+    }
 
-        if let Some(alias_str) = alias {
-            let mut sys_bin_file = app_dir.get_alias_dir().join(alias_str);
-            sys_bin_file.set_extension(EXE_EXTENSION);
-            if sys_bin_file.exists() && sys_bin_file.is_file() {
-                remove_link(&sys_bin_file)?;
+    // aliases of the legacy directory are moved to the new one before it's removed
+    let legacy = legacy_dir.is_dir();
+    if legacy {
+        for name in legacy_aliases(app_dir, &package.name, &release.version, &legacy_dir) {
+            if aliases.contains(&name) {
+                continue;
             }
-            make_link(&sys_bin_file, &package_bin_file)?;
+            let mut alias_file = app_dir.get_alias_dir().join(&name);
+            alias_file.set_extension(EXE_EXTENSION);
+            transaction.swap_link(&alias_file, &package_bin_file)?;
+            aliases.push(name);
         }
+        transaction.remove_dir(&legacy_dir)?;
+    }
+    transaction.commit();
 
-        let _ = remove_dir_all(tmp_dir);
-        Ok(())
+    let receipt = Receipt {
+        version: release.version.clone(),
+        bin_name: package.bin_name.clone(),
+        source_url: Some(asset.download_url.clone()),
+        asset_name: Some(asset.name.clone()),
+        sha256,
+        aliases,
+        installed_at: unix_now(),
+        build: Some(BuildKind::Binary),
+    };
+    let legacy_version = format!("v{}", release.version);
+    let result = PackageReceipts::load(app_dir, &package.name).and_then(|mut receipts| {
+        receipts.record(receipt);
+        if legacy {
+            receipts.versions.retain(|r| r.version != legacy_version);
+        }
+        receipts.save(app_dir)
+    });
+    if let Err(e) = result {
+        error!("failed to save the receipt of {}: {}", package.name, e);
     }
+    Ok(release.version)
 }
 
-impl<'a, 'b> Installer for RustBinInstaller<'a, 'b> {
-    async fn install(
+/// aliases linked to the legacy directory, both recorded in receipts and linked before receipts.
+fn legacy_aliases(
+    app_dir: &AppDir,
+    name: &str,
+    version: &str,
+    legacy_dir: &Path,
+) -> BTreeSet<String> {
+    let mut aliases: BTreeSet<String> = match PackageReceipts::load(app_dir, name) {
+        Ok(receipts) => receipts
+            .alias_map()
+            .into_iter()
+            .filter(|(_, v)| normalize_version(v) == version)
+            .map(|(a, _)| a)
+            .collect(),
+        Err(e) => {
+            error!("failed to load receipts of {}: {}", name, e);
+            BTreeSet::new()
+        }
+    };
+    if let Ok(entries) = read_dir(app_dir.get_alias_dir()) {
+        for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+            if !read_link(&path).is_ok_and(|t| t.starts_with(legacy_dir)) {
+                continue;
+            }
+            if let Some(a) = path.file_stem().and_then(|s| s.to_str()) {
+                // backups and temporary links of transactions
+                if !a.starts_with('.') {
+                    aliases.insert(a.to_string());
+                }
+            }
+        }
+    }
+    aliases
+}
+
+impl<'a, 'b> RustBinInstaller<'a, 'b> {
+    pub fn new(global_input: &'b mut GlobalInput<'a>) -> Self {
+        RustBinInstaller { global_input }
+    }
+
+    /// link a prepared package, or build it from source code if the user agrees, installs are finished one by one.
+    pub async fn finish(
         &mut self,
+        prepared: Prepared,
         package: &Package,
         request: &InstallRequest,
         app_dir: &AppDir,
-        context: &InstallerContext,
-    ) {
-        let final_version = match &request.version {
-            Some(v) => v,
-            None => LATEST_VERSION,
-        };
-        let app_release = match find_assets(
-            package,
-            final_version,
-            &package.remote_file_prefix,
-            &context.rust_abi,
-        )
-        .await
-        {
-            Ok(r) => r,
-            Err(e) => {
-                error!("failed to get assets: {}", e);
-                return;
-            }
-        };
-
-        if !app_release.assets.is_empty() {
-            if let Err(e) = self.install_package(&app_release, package, &request.alias, app_dir, context.force)
-                .await {
-                    println!("{}", format!("failed to install {}: {}", package.name, e).red());
-                } else {
-                        println!(
-                            "the package {} with version {} has been installed",
-                            package.name.green(),
-                            app_release.version.green()
-                        );
-                }
-        } else if app_release.source_url.is_some() {
-            print!("cannot find appropriate package to install, would you like to install from source code? [y/n]");
-            let _ = std::io::stdout().flush();
-            let answer = match self.global_input.read_line() {
-                Ok(a) => a,
-                Err(e) => {
-                    error!("failed to read from command: {}", e);
-                    return;
-                }
-            };
-            debug!("install from source code? {}", answer);
-            if answer == "y" {
-                let src_installer = RustSrcInstaller {};
-                if let Err(e) = src_installer
-                    .install_package(&app_release, package, &request.alias, app_dir)
-                    .await {
-                        println!("{}", format!("failed to install from source code {}: {}", package.name, e).red());
-                    } else {
+    ) -> InstallStatus {
+        let app_release = match prepared {
+            Prepared::Staged(staged) => {
+                return match activate(*staged, package, &request.alias, app_dir) {
+                    Err(e) => {
+                        println!("{}", format!("failed to install {}: {}", package.name, e).red());
+                        InstallStatus::Failed
+                    }
+                    Ok(version) => {
                         println!(
                             "the package {} with version {} has been installed",
                             package.name.green(),
-                            app_release.version.green()
+                            version.green()
                         );
+                        InstallStatus::Installed(version)
                     }
+                };
+            }
+            Prepared::Unchanged(version) => return InstallStatus::Unchanged(version),
+            Prepared::Failed => return InstallStatus::Failed,
+            Prepared::Source(r) => r,
+        };
+
+        print!("cannot find appropriate package to install, would you like to install from source code? [y/n]");
+        let _ = std::io::stdout().flush();
+        let answer = match self.global_input.read_line() {
+            Ok(a) => a,
+            Err(e) => {
+                error!("failed to read from command: {}", e);
+                return InstallStatus::Failed;
+            }
+        };
+        debug!("install from source code? {}", answer);
+        if answer != "y" {
+            return InstallStatus::Failed;
+        }
+        let src_installer = RustSrcInstaller {};
+        if let Err(e) = src_installer
+            .install_package(&app_release, package, &request.alias, app_dir)
+            .await {
+                println!("{}", format!("failed to install from source code {}: {}", package.name, e).red());
+                InstallStatus::Failed
+            } else {
+                println!(
+                    "the package {} with version {} has been installed",
+                    package.name.green(),
+                    app_release.version.green()
+                );
+                InstallStatus::Installed(app_release.version)
             }
-        } else {
-            println!("{}", "cannot find appropriate package to install".red());
+    }
+}
+
+/// a published crate is preferred to the repository archive when building from source code.
+async fn find_crate_source(package: &Package, release: &AppRelease) -> Option<AppSource> {
+    let crates_io = package.crates_io.as_ref()?;
+    if release
+        .source
+        .as_ref()
+        .is_some_and(|s| s.file_name.ends_with(".crate"))
+    {
+        return None;
+    }
+    let provider = CratesIoProvider::new(crates_io.clone(), &package.name);
+    match provider.find_release(&release.version).await {
+        Ok(r) => r.and_then(|r| r.source),
+        Err(e) => {
+            error!("failed to find crate {}: {}", package.name, e);
+            None
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::action::installer::{
+        install::{to_github_arch, to_github_os, AppAsset},
+        provider::{MockReleaseProvider, RemoteRelease},
+        test::archive,
+    };
+    use crate::tool::{fs::make_link, http::HttpError, state::PackageReceipts};
+    use mockito::Server;
+    use std::env::consts::{ARCH, EXE_SUFFIX, OS};
+    use std::fs::{create_dir_all, read_to_string, write};
+    use tempfile::tempdir;
+
+    fn package() -> Package {
+        toml::from_str(
+            r#"
+            name = "tool"
+            url = "https://example.com/tool"
+            language = "rust"
+            bin_name = "tool"
+            "#,
+        )
+        .unwrap()
+    }
+
+    fn context() -> InstallerContext {
+        InstallerContext {
+            rust_abi: "gnu".to_string(),
+            force: false,
+            jobs: 1,
+            ..Default::default()
+        }
+    }
+
+    fn request() -> InstallRequest {
+        InstallRequest {
+            name: "tool".to_string(),
+            alias: Some("tl".to_string()),
+            version: None,
+            prerelease: false,
+        }
+    }
+
+    fn tool_archive() -> Vec<u8> {
+        archive(&["tool-1.0.0/", &format!("tool-1.0.0/tool{}", EXE_SUFFIX)])
+    }
+
+    async fn install(
+        global_input: &mut GlobalInput<'_>,
+        provider: &MockReleaseProvider,
+        app_dir: &AppDir,
+        context: &InstallerContext,
+    ) -> InstallStatus {
+        let pb = ProgressBar::hidden();
+        let prepared = prepare(provider, &package(), &request(), app_dir, context, &pb).await;
+        RustBinInstaller::new(global_input)
+            .finish(prepared, &package(), &request(), app_dir)
+            .await
+    }
+
+    fn provider(release: Option<RemoteRelease>) -> MockReleaseProvider {
+        let mut provider = MockReleaseProvider::new();
+        provider
+            .expect_find_release()
+            .withf(|v| v == LATEST_VERSION)
+            .returning(move |_| Ok(release.clone()));
+        provider
+            .expect_assets()
+            .returning(|r| Ok(r.assets.clone()));
+        provider.expect_is_platform_specific().return_const(false);
+        provider
+    }
+
+    #[tokio::test]
+    async fn test_install_from_provider() {
+        let mut server = Server::new_async().await;
+        let mock = server
+            .mock("GET", "/tool.tar.gz")
+            .with_body(tool_archive())
+            .expect(1)
+            .create_async()
+            .await;
+        let asset_name = format!("tool-{}-{}-gnu.tar.gz", to_github_arch(ARCH), to_github_os(OS));
+        let provider = provider(Some(RemoteRelease {
+            tag_name: "v1.0.0".to_string(),
+            assets: vec![
+                AppAsset {
+                    name: "tool-unknown-os.tar.gz".to_string(),
+                    download_url: format!("{}/other.tar.gz", server.url()),
+                    bin_path: None,
+                },
+                AppAsset {
+                    name: asset_name,
+                    download_url: format!("{}/tool.tar.gz", server.url()),
+                    bin_path: None,
+                },
+            ],
+            ..Default::default()
+        }));
+
+        let dir = tempdir().unwrap();
+        let app_dir = AppDir::from_home(dir.path().join("home")).unwrap();
+        let mut stdin = std::io::stdin();
+        let mut global_input = GlobalInput::new(&mut stdin);
+
+        let status = install(&mut global_input, &provider, &app_dir, &context()).await;
+        assert_eq!(InstallStatus::Installed("1.0.0".to_string()), status);
+
+        let mut bin_file = app_dir
+            .get_packages_dir()
+            .join("tool")
+            .join("1.0.0")
+            .join("tool");
+        bin_file.set_extension(EXE_EXTENSION);
+        assert!(bin_file.is_file());
+        let mut link = app_dir.get_bin_dir().join("tool");
+        link.set_extension(EXE_EXTENSION);
+        assert!(link.exists());
+        let mut alias = app_dir.get_alias_dir().join("tl");
+        alias.set_extension(EXE_EXTENSION);
+        assert!(alias.exists());
+
+        let receipts = PackageReceipts::load(&app_dir, "tool").unwrap();
+        let receipt = receipts.current_receipt().unwrap();
+        assert_eq!("1.0.0", receipt.version);
+        assert_eq!(format!("{}/tool.tar.gz", server.url()), receipt.source_url.as_deref().unwrap());
+        assert_eq!(64, receipt.sha256.as_ref().unwrap().len());
+        assert_eq!(vec!["tl"], receipt.aliases);
+        assert_eq!(Some(BuildKind::Binary), receipt.build);
+
+        // a reinstall uses the cached asset
+        let context = InstallerContext {
+            force: true,
+            ..context()
+        };
+        let status = install(&mut global_input, &provider, &app_dir, &context).await;
+        assert_eq!(InstallStatus::Installed("1.0.0".to_string()), status);
+
+        // the receipt and the cached asset are used if the release cannot be found
+        let mut offline = MockReleaseProvider::new();
+        offline
+            .expect_find_release()
+            .returning(|_| Err(InstallError::Fetch("releases", HttpError::Timeout)));
+        let status = install(&mut global_input, &offline, &app_dir, &context).await;
+        assert_eq!(InstallStatus::Installed("1.0.0".to_string()), status);
+        // but not if the release is found and it's wrong
+        let mut broken = MockReleaseProvider::new();
+        broken
+            .expect_find_release()
+            .returning(|_| Err(InstallError::GeneralStr("invalid requirement")));
+        let status = install(&mut global_input, &broken, &app_dir, &context).await;
+        assert_eq!(InstallStatus::Failed, status);
+        mock.assert_async().await;
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[tokio::test]
+    async fn test_reinstall_moves_legacy_aliases() {
+        let mut server = Server::new_async().await;
+        server
+            .mock("GET", "/tool.tar.gz")
+            .with_body(tool_archive())
+            .create_async()
+            .await;
+        let asset_name = format!("tool-{}-{}-gnu.tar.gz", to_github_arch(ARCH), to_github_os(OS));
+        let provider = provider(Some(RemoteRelease {
+            tag_name: "v1.0.0".to_string(),
+            assets: vec![AppAsset {
+                name: asset_name,
+                download_url: format!("{}/tool.tar.gz", server.url()),
+                bin_path: None,
+            }],
+            ..Default::default()
+        }));
+
+        // installed before versions were normalized, with an alias from receipts and one from before receipts
+        let dir = tempdir().unwrap();
+        let app_dir = AppDir::from_home(dir.path().join("home")).unwrap();
+        let legacy_dir = app_dir.get_packages_dir().join("tool").join("v1.0.0");
+        create_dir_all(&legacy_dir).unwrap();
+        write(legacy_dir.join("tool"), "old").unwrap();
+        for link in [
+            app_dir.get_bin_dir().join("tool"),
+            app_dir.get_alias_dir().join("recorded"),
+            app_dir.get_alias_dir().join("unrecorded"),
+        ] {
+            make_link(&link, &legacy_dir.join("tool")).unwrap();
+        }
+        let mut receipts = PackageReceipts::load(&app_dir, "tool").unwrap();
+        receipts.record(Receipt {
+            version: "v1.0.0".to_string(),
+            bin_name: "tool".to_string(),
+            source_url: None,
+            asset_name: None,
+            sha256: None,
+            aliases: vec!["recorded".to_string()],
+            installed_at: 0,
+            build: None,
+        });
+        receipts.save(&app_dir).unwrap();
+        let mut stdin = std::io::stdin();
+        let mut global_input = GlobalInput::new(&mut stdin);
+
+        let context = InstallerContext {
+            force: true,
+            ..context()
+        };
+        let status = install(&mut global_input, &provider, &app_dir, &context).await;
+
+        assert_eq!(InstallStatus::Installed("1.0.0".to_string()), status);
+        assert!(!legacy_dir.exists());
+        let bin_file = app_dir
+            .get_packages_dir()
+            .join("tool")
+            .join("1.0.0")
+            .join("tool");
+        for alias in ["tl", "recorded", "unrecorded"] {
+            let link = app_dir.get_alias_dir().join(alias);
+            assert_eq!(bin_file, std::fs::read_link(&link).unwrap());
+        }
+        let receipts = PackageReceipts::load(&app_dir, "tool").unwrap();
+        assert_eq!(
+            vec!["1.0.0"],
+            receipts
+                .versions
+                .iter()
+                .map(|r| r.version.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec!["tl", "recorded", "unrecorded"],
+            receipts.versions[0].aliases
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_install_keeps_previous() {
+        let mut server = Server::new_async().await;
+        let empty = archive(&[]);
+        server
+            .mock("GET", "/tool.tar.gz")
+            .with_body(empty)
+            .create_async()
+            .await;
+        let asset_name = format!("tool-{}-{}-gnu.tar.gz", to_github_arch(ARCH), to_github_os(OS));
+        let provider = provider(Some(RemoteRelease {
+            tag_name: "v1.0.0".to_string(),
+            assets: vec![AppAsset {
+                name: asset_name,
+                download_url: format!("{}/tool.tar.gz", server.url()),
+                bin_path: None,
+            }],
+            ..Default::default()
+        }));
+
+        let dir = tempdir().unwrap();
+        let app_dir = AppDir::from_home(dir.path().join("home")).unwrap();
+        let version_dir = app_dir.get_packages_dir().join("tool").join("1.0.0");
+        create_dir_all(&version_dir).unwrap();
+        let mut old_bin = version_dir.join("tool");
+        old_bin.set_extension(EXE_EXTENSION);
+        write(&old_bin, "old").unwrap();
+        let mut link = app_dir.get_bin_dir().join("tool");
+        link.set_extension(EXE_EXTENSION);
+        make_link(&link, &old_bin).unwrap();
+        let mut stdin = std::io::stdin();
+        let mut global_input = GlobalInput::new(&mut stdin);
+
+        let context = InstallerContext {
+            force: true,
+            ..context()
+        };
+        let status = install(&mut global_input, &provider, &app_dir, &context).await;
+
+        assert_eq!(InstallStatus::Failed, status);
+        assert_eq!("old", read_to_string(&link).unwrap());
+        assert_eq!(
+            vec!["1.0.0"],
+            std::fs::read_dir(app_dir.get_packages_dir().join("tool"))
+                .unwrap()
+                .map(|e| e.unwrap().file_name().into_string().unwrap())
+                .collect::<Vec<_>>()
+        );
+        let mut alias = app_dir.get_alias_dir().join("tl");
+        alias.set_extension(EXE_EXTENSION);
+        assert!(alias.symlink_metadata().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_skip_without_release() {
+        let provider = provider(None);
+
+        let dir = tempdir().unwrap();
+        let app_dir = AppDir::from_home(dir.path().join("home")).unwrap();
+        let mut stdin = std::io::stdin();
+        let mut global_input = GlobalInput::new(&mut stdin);
+
+        let status = install(&mut global_input, &provider, &app_dir, &context()).await;
+
+        assert_eq!(InstallStatus::Failed, status);
+        assert!(!app_dir.get_packages_dir().join("tool").exists());
+    }
+
+    #[test]
+    fn test_unpack_bin_at_archive_root() {
+        let dir = tempdir().unwrap();
+        let to_file = dir.path().join("tool.tar.gz");
+        write(&to_file, archive(&[&format!("tool{}", EXE_SUFFIX)])).unwrap();
+        let asset = AppAsset {
+            name: "tool.tar.gz".to_string(),
+            download_url: "https://example.com/tool.tar.gz".to_string(),
+            bin_path: Some(format!("tool-x86_64-unknown-linux-gnu-v1.0.0/tool{}", EXE_SUFFIX)),
+        };
+        let tmp_dir = dir.path().join("tmp");
+        create_dir_all(&tmp_dir).unwrap();
+        let staged_bin_file = dir.path().join("staged");
+
+        let sha256 = unpack(&asset, &package(), &to_file, &tmp_dir, &staged_bin_file).unwrap();
+        assert!(sha256.is_some());
+        assert!(staged_bin_file.is_file());
+    }
+}
@@ -1,40 +1,50 @@
-use super::install::{AppRelease, InstallError};
+use super::install::{save_receipt, AppRelease, InstallError};
+use super::transaction::{hidden_sibling, verify_bin, Transaction};
 use crate::tool::{
-    fs::{decompress, make_link, remove_link, AppDir},
+    cache::download_cached,
+    fs::{decompress, sha256_file, AppDir},
+    http::download_bar,
     kaleido::Package,
-    http::download_with_progress,
+    state::{unix_now, BuildKind, Receipt},
 };
 use log::{debug, error};
 use std::env::consts::EXE_EXTENSION;
-use std::fs::{copy, create_dir_all, remove_dir_all, remove_file};
+use std::fs::{copy, create_dir_all};
 
 pub struct RustSrcInstaller {}
 
 impl RustSrcInstaller {
-    pub async fn install_package(&self, app_release: &AppRelease, package: &Package, alias: &Option<String>, app_dir: &AppDir) -> std::result::Result<(), InstallError> {
-        let source_url = app_release.source_url.as_ref().unwrap();
-        let tmp_dir = app_dir.get_home_dir().join("tmp");
-        let file_name = format!("{}.zip", app_release.version);
-        let to_file = tmp_dir.join(&file_name);
-        debug!("download {} to {:?}", source_url, to_file);
-        if to_file.exists() && to_file.is_file() {
-            remove_file(&to_file)?;
-        }
+    pub async fn install_package(
+        &self,
+        app_release: &AppRelease,
+        package: &Package,
+        alias: &Option<String>,
+        app_dir: &AppDir,
+    ) -> std::result::Result<(), InstallError> {
+        let source = match app_release.source.as_ref() {
+            Some(s) => s,
+            None => return Err(InstallError::GeneralStr("no source code to build")),
+        };
+        let tmp = app_dir.create_tmp_dir(&package.name)?;
+        let tmp_dir = tmp.path();
+        let file_name = &source.file_name;
+        let to_file = tmp_dir.join(file_name);
+        debug!("download {} to {:?}", source.url, to_file);
 
         let src_path = tmp_dir.join(&package.name);
-        if src_path.exists() && src_path.is_dir() {
-            remove_dir_all(&src_path)?;
-        }
         create_dir_all(&src_path)?;
 
-        download_with_progress(to_file, source_url).await?;
-
+        download_cached(
+            app_dir,
+            &to_file,
+            &source.url,
+            app_release.identity.as_deref(),
+            &download_bar(&package.name),
+        )
+        .await?;
 
-        let to_file = tmp_dir.join(&file_name);
-        let top_folder = match decompress(&to_file, &src_path)? {
-            Some(f) => f,
-            None => String::new(),
-        };
+        let to_file = tmp_dir.join(file_name);
+        let top_folder = decompress(&to_file, &src_path)?.unwrap_or_default();
 
         let src_folder = src_path.join(top_folder);
         let build_result = std::process::Command::new("cargo")
@@ -43,52 +53,63 @@ impl RustSrcInstaller {
             .arg("--release")
             .current_dir(&src_folder)
             .status();
-        match build_result {
-            Ok(status) => {
-                if !status.success() {
-                    error!("failed to build {} from source code", package.name);
-                    return Err(InstallError::GeneralStr("failed to build from source code"));
-                }
-                let package_dir = app_dir
-                    .get_packages_dir()
-                    .join(&package.name)
-                    .join(&app_release.version);
-                if package_dir.exists() && package_dir.is_dir() {
-                    remove_dir_all(&package_dir)?;
-                }
-                create_dir_all(&package_dir)?;
-
-                let mut package_bin_file = package_dir.join(&package.bin_name);
-                package_bin_file.set_extension(EXE_EXTENSION);
-                let mut bin_file = src_folder
-                    .join("target")
-                    .join("release")
-                    .join(&package.bin_name);
-                bin_file.set_extension(EXE_EXTENSION);
-                copy(&bin_file, &package_bin_file)?;
-
-                let mut sys_bin_file = app_dir.get_bin_dir().join(&package.bin_name);
-                sys_bin_file.set_extension(EXE_EXTENSION);
-                if sys_bin_file.exists() && sys_bin_file.is_file() {
-                    remove_link(&sys_bin_file)?;
-                }
-                make_link(&sys_bin_file, &package_bin_file)?;
-
-                if let Some(alias_str) = alias {
-                    let mut sys_bin_file = app_dir.get_alias_dir().join(alias_str);
-                    sys_bin_file.set_extension(EXE_EXTENSION);
-                    if sys_bin_file.exists() && sys_bin_file.is_file() {
-                        remove_link(&sys_bin_file)?;
-                    }
-                    make_link(&sys_bin_file, &package_bin_file)?;
-                }
-
-                let _ = remove_dir_all(tmp_dir);
-            }
+        let status = match build_result {
+            Ok(s) => s,
             Err(e) => {
                 error!("failed to compile the source: {}", e);
+                return Err(InstallError::General(format!("failed to run cargo: {}", e)));
             }
+        };
+        if !status.success() {
+            error!("failed to build {} from source code", package.name);
+            return Err(InstallError::GeneralStr("failed to build from source code"));
+        }
+        let package_dir = app_dir
+            .get_packages_dir()
+            .join(&package.name)
+            .join(&app_release.version);
+        let mut package_bin_file = package_dir.join(&package.bin_name);
+        package_bin_file.set_extension(EXE_EXTENSION);
+
+        let mut transaction = Transaction::new();
+        let staging_dir = hidden_sibling(&package_dir, "staging");
+        transaction.create_dir(&staging_dir)?;
+        let mut staged_bin_file = staging_dir.join(&package.bin_name);
+        staged_bin_file.set_extension(EXE_EXTENSION);
+        let mut bin_file = src_folder
+            .join("target")
+            .join("release")
+            .join(&package.bin_name);
+        bin_file.set_extension(EXE_EXTENSION);
+        copy(&bin_file, &staged_bin_file)?;
+        verify_bin(&staged_bin_file)?;
+
+        transaction.replace_dir(&staging_dir, &package_dir)?;
+        let mut sys_bin_file = app_dir.get_bin_dir().join(&package.bin_name);
+        sys_bin_file.set_extension(EXE_EXTENSION);
+        transaction.swap_link(&sys_bin_file, &package_bin_file)?;
+
+        if let Some(alias_str) = alias {
+            let mut sys_bin_file = app_dir.get_alias_dir().join(alias_str);
+            sys_bin_file.set_extension(EXE_EXTENSION);
+            transaction.swap_link(&sys_bin_file, &package_bin_file)?;
         }
+        transaction.commit();
+
+        save_receipt(
+            app_dir,
+            package,
+            Receipt {
+                version: app_release.version.clone(),
+                bin_name: package.bin_name.clone(),
+                source_url: Some(source.url.clone()),
+                asset_name: Some(source.file_name.clone()),
+                sha256: sha256_file(&to_file).ok(),
+                aliases: alias.iter().cloned().collect(),
+                installed_at: unix_now(),
+                build: Some(BuildKind::Source),
+            },
+        );
 
         Ok(())
     }
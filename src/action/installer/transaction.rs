@@ -0,0 +1,265 @@
+use super::install::{InstallError, Result};
+use crate::tool::fs::{copy_link, remove_link, swap_link};
+use log::{debug, error};
+use std::fs::{create_dir_all, metadata, remove_dir_all, remove_file, rename};
+use std::path::{Path, PathBuf};
+
+/// changes made by an install, they are undone in reverse order if the transaction is dropped without commit.
+pub struct Transaction {
+    undo: Vec<Undo>,
+    committed: bool,
+}
+
+enum Undo {
+    /// a created directory
+    RemoveDir(PathBuf),
+    /// a created link
+    RemoveLink(PathBuf),
+    /// a directory or link moved to a backup, it's moved back
+    Restore { backup: PathBuf, to: PathBuf },
+}
+
+impl Transaction {
+    pub fn new() -> Self {
+        Transaction {
+            undo: vec![],
+            committed: false,
+        }
+    }
+
+    /// create an empty directory, an existing one is removed first.
+    pub fn create_dir(&mut self, dir: &Path) -> Result<()> {
+        if dir.exists() {
+            remove_dir_all(dir)?;
+        }
+        create_dir_all(dir)?;
+        self.undo.push(Undo::RemoveDir(dir.to_path_buf()));
+        Ok(())
+    }
+
+    /// move the staged directory to the target, an existing target is kept as a backup until commit.
+    pub fn replace_dir(&mut self, staged: &Path, target: &Path) -> Result<()> {
+        if target.exists() {
+            let backup = hidden_sibling(target, "backup");
+            if backup.exists() {
+                remove_dir_all(&backup)?;
+            }
+            rename(target, &backup)?;
+            self.undo.push(Undo::Restore {
+                backup,
+                to: target.to_path_buf(),
+            });
+        }
+        rename(staged, target)?;
+        self.undo.push(Undo::RemoveDir(target.to_path_buf()));
+        Ok(())
+    }
+
+    /// move the directory to a backup, it's removed on commit.
+    pub fn remove_dir(&mut self, dir: &Path) -> Result<()> {
+        let backup = hidden_sibling(dir, "backup");
+        if backup.exists() {
+            remove_dir_all(&backup)?;
+        }
+        rename(dir, &backup)?;
+        self.undo.push(Undo::Restore {
+            backup,
+            to: dir.to_path_buf(),
+        });
+        Ok(())
+    }
+
+    /// point the link to the origin, the previous link is kept as a backup until commit.
+    pub fn swap_link(&mut self, link: &Path, origin: &Path) -> Result<()> {
+        if link.symlink_metadata().is_ok() {
+            let backup = hidden_sibling(link, "backup");
+            if backup.symlink_metadata().is_ok() {
+                remove_link(&backup)?;
+            }
+            copy_link(link, &backup)?;
+            self.undo.push(Undo::Restore {
+                backup,
+                to: link.to_path_buf(),
+            });
+        } else {
+            self.undo.push(Undo::RemoveLink(link.to_path_buf()));
+        }
+        swap_link(link, origin)?;
+        Ok(())
+    }
+
+    /// remove the link, it's kept as a backup until commit.
+    pub fn remove_link(&mut self, link: &Path) -> Result<()> {
+        let backup = hidden_sibling(link, "backup");
+        if backup.symlink_metadata().is_ok() {
+            remove_link(&backup)?;
+        }
+        copy_link(link, &backup)?;
+        remove_link(link)?;
+        self.undo.push(Undo::Restore {
+            backup,
+            to: link.to_path_buf(),
+        });
+        Ok(())
+    }
+
+    /// keep all changes and remove backups.
+    pub fn commit(mut self) {
+        self.committed = true;
+        for undo in self.undo.drain(..) {
+            if let Undo::Restore { backup, .. } = undo {
+                let result = if backup.is_symlink() || backup.is_file() {
+                    remove_link(&backup).map_err(InstallError::from)
+                } else {
+                    remove_dir_all(&backup).map_err(InstallError::from)
+                };
+                if let Err(e) = result {
+                    error!("failed to remove backup {:?}: {}", backup, e);
+                }
+            }
+        }
+    }
+
+    fn rollback(&mut self) {
+        while let Some(undo) = self.undo.pop() {
+            let result = match &undo {
+                Undo::RemoveDir(dir) => {
+                    debug!("rollback: remove {:?}", dir);
+                    if dir.exists() {
+                        remove_dir_all(dir)
+                    } else {
+                        Ok(())
+                    }
+                }
+                Undo::RemoveLink(link) => {
+                    debug!("rollback: remove link {:?}", link);
+                    if link.symlink_metadata().is_ok() {
+                        remove_file(link)
+                    } else {
+                        Ok(())
+                    }
+                }
+                Undo::Restore { backup, to } => {
+                    debug!("rollback: restore {:?} to {:?}", backup, to);
+                    match metadata(to) {
+                        // links are replaced by rename
+                        Ok(m) if m.is_dir() && !to.is_symlink() => {
+                            remove_dir_all(to).and_then(|_| rename(backup, to))
+                        }
+                        _ => rename(backup, to),
+                    }
+                }
+            };
+            if let Err(e) = result {
+                error!("failed to roll back the installation: {}", e);
+            }
+        }
+    }
+}
+
+impl Drop for Transaction {
+    fn drop(&mut self) {
+        if !self.committed {
+            self.rollback();
+        }
+    }
+}
+
+/// e.g. 'packages/tool/.1.0.0.backup' for 'packages/tool/1.0.0'.
+pub fn hidden_sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = std::ffi::OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(".");
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// the file exists and isn't empty, it's made executable on unix.
+pub fn verify_bin(bin_file: &Path) -> Result<()> {
+    let m = match metadata(bin_file) {
+        Ok(m) if m.is_file() && m.len() > 0 => m,
+        _ => {
+            return Err(InstallError::General(format!(
+                "cannot find the executable file {:?}",
+                bin_file
+            )))
+        }
+    };
+    #[cfg(not(target_os = "windows"))]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mut permissions = m.permissions();
+        if permissions.mode() & 0o111 == 0 {
+            permissions.set_mode(permissions.mode() | 0o755);
+            std::fs::set_permissions(bin_file, permissions)?;
+        }
+    }
+    #[cfg(target_os = "windows")]
+    let _ = m;
+    Ok(())
+}
+
+#[cfg(all(test, not(target_os = "windows")))]
+mod test {
+    use super::*;
+    use crate::tool::fs::make_link;
+    use std::fs::{read_link, read_to_string, write};
+
+    #[test]
+    fn test_rollback() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("1.0.0");
+        create_dir_all(&target).unwrap();
+        write(target.join("tool"), "old").unwrap();
+        let link = dir.path().join("tool");
+        make_link(&link, &target.join("tool")).unwrap();
+
+        let staged = hidden_sibling(&target, "staging");
+        let mut tx = Transaction::new();
+        tx.create_dir(&staged).unwrap();
+        write(staged.join("tool"), "new").unwrap();
+        tx.replace_dir(&staged, &target).unwrap();
+        tx.swap_link(&link, &target.join("tool")).unwrap();
+        tx.swap_link(&dir.path().join("alias"), &target.join("tool"))
+            .unwrap();
+        assert_eq!("new", read_to_string(&link).unwrap());
+        let removed = dir.path().join("removed");
+        make_link(&removed, &target.join("tool")).unwrap();
+        tx.remove_link(&removed).unwrap();
+        assert!(removed.symlink_metadata().is_err());
+        let legacy = dir.path().join("v1.0.0");
+        create_dir_all(&legacy).unwrap();
+        tx.remove_dir(&legacy).unwrap();
+        assert!(!legacy.exists());
+        drop(tx);
+
+        assert_eq!("old", read_to_string(&link).unwrap());
+        assert_eq!(target.join("tool"), read_link(&link).unwrap());
+        assert!(!dir.path().join("alias").exists());
+        assert!(!staged.exists());
+        assert!(target.is_dir());
+        assert!(legacy.is_dir());
+        assert_eq!(target.join("tool"), read_link(&removed).unwrap());
+        assert!(!hidden_sibling(&target, "backup").exists());
+    }
+
+    #[test]
+    fn test_commit() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("1.0.0");
+        create_dir_all(&target).unwrap();
+        write(target.join("tool"), "old").unwrap();
+
+        let staged = hidden_sibling(&target, "staging");
+        let mut tx = Transaction::new();
+        tx.create_dir(&staged).unwrap();
+        write(staged.join("tool"), "new").unwrap();
+        verify_bin(&staged.join("tool")).unwrap();
+        tx.replace_dir(&staged, &target).unwrap();
+        tx.commit();
+
+        assert_eq!("new", read_to_string(target.join("tool")).unwrap());
+        assert!(!hidden_sibling(&target, "backup").exists());
+        assert!(verify_bin(&dir.path().join("missing")).is_err());
+    }
+}
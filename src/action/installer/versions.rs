@@ -0,0 +1,416 @@
+use super::{
+    binstall::{binstall_templates, SourceLoader, SourceMetadata, Templates},
+    install::{platform_keys, select_release_assets, Result},
+    provider::{normalize_version, PackageProvider, ReleaseProvider, RemoteRelease, TagFormat},
+};
+use crate::action::helper::{get_installed_packages, installed_versions};
+use crate::tool::{
+    fs::AppDir,
+    http,
+    kaleido::{self, Binstall, Package},
+};
+use colored::Colorize;
+use futures_util::{stream, StreamExt};
+use indicatif::ProgressBar;
+use log::{debug, error};
+use serde::Serialize;
+
+/// how many releases are checked at the same time.
+const CONCURRENCY: usize = 8;
+
+/// a remote release of a package.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct VersionInfo {
+    pub version: String,
+    pub tag_name: String,
+    pub published_at: Option<String>,
+    pub prerelease: bool,
+    /// true if there is an asset for current platform and abi.
+    pub has_asset: bool,
+    pub installed: bool,
+    /// true if it's the version linked to the bin directory.
+    pub current: bool,
+}
+
+/// list remote releases of a package.
+pub async fn versions(name: String, app_dir: &AppDir, rust_abi: &str, json: bool) {
+    let kaleido = match kaleido::load_config(app_dir.get_home_dir()) {
+        Ok(k) => k,
+        Err(e) => {
+            error!("Failed to load metadata: {}", e);
+            return;
+        }
+    };
+    let package = match kaleido.packages.iter().find(|p| p.name == name) {
+        Some(p) => p.for_platform(&platform_keys(rust_abi)),
+        None => {
+            println!(
+                "{}",
+                format!("cannot find package with name {}", name).red()
+            );
+            return;
+        }
+    };
+    let provider = match PackageProvider::from_package(&package, rust_abi) {
+        Ok(p) => p,
+        Err(e) => {
+            println!(
+                "{}",
+                format!("cannot list versions of {}: {}", name, e).red()
+            );
+            return;
+        }
+    };
+
+    let installed: Vec<String> = installed_versions(app_dir, &package.name)
+        .iter()
+        .map(|v| normalize_version(v).to_string())
+        .collect();
+    let current = match get_installed_packages(app_dir).await {
+        Ok(packages) => packages
+            .into_iter()
+            .find(|p| p.package.name == package.name)
+            .map(|p| normalize_version(&p.version).to_string()),
+        Err(e) => {
+            debug!("failed to get installed packages: {}", e);
+            None
+        }
+    };
+
+    let tmp = match app_dir.create_tmp_dir(&package.name) {
+        Ok(t) => t,
+        Err(e) => {
+            error!("failed to create temporary directory: {}", e);
+            return;
+        }
+    };
+    let metadata = SourceMetadata::default();
+    // nothing is printed but versions, e.g. in json
+    let pb = ProgressBar::hidden();
+    let loader = SourceLoader {
+        app_dir,
+        tmp_dir: tmp.path(),
+        metadata: &metadata,
+        pb: &pb,
+    };
+    let versions = match collect_versions(
+        &provider,
+        &package,
+        rust_abi,
+        &installed,
+        current.as_deref(),
+        &loader,
+    )
+    .await
+    {
+        Ok(v) => v,
+        Err(e) => {
+            println!(
+                "{}",
+                format!("failed to list versions of {}: {}", name, e).red()
+            );
+            return;
+        }
+    };
+
+    if json {
+        match serde_json::to_string_pretty(&versions) {
+            Ok(s) => println!("{}", s),
+            Err(e) => error!("failed to serialize versions: {}", e),
+        }
+        return;
+    }
+    for v in versions {
+        let mark = if v.current { "*" } else { " " };
+        let date = v
+            .published_at
+            .as_deref()
+            .map(|d| d.chars().take(10).collect::<String>())
+            .unwrap_or_default();
+        let mut flags = vec![];
+        if v.prerelease {
+            flags.push("prerelease".yellow().to_string());
+        }
+        if v.installed {
+            flags.push("installed".green().to_string());
+        }
+        if !v.has_asset {
+            flags.push("no asset".red().to_string());
+        }
+        let version = if v.installed {
+            v.version.green()
+        } else {
+            v.version.normal()
+        };
+        println!("{} {:<20} {:<10} {}", mark, version, date, flags.join(", "));
+    }
+}
+
+/// releases which are not drafts, the latest one is the first.
+/// binstall templates are read from the latest release only, then releases are checked at the same time.
+async fn collect_versions<P: ReleaseProvider>(
+    provider: &P,
+    package: &Package,
+    rust_abi: &str,
+    installed: &[String],
+    current: Option<&str>,
+    loader: &SourceLoader<'_>,
+) -> Result<Vec<VersionInfo>> {
+    let tag_format = TagFormat::from_package(package);
+    let mut releases = vec![];
+    for release in provider.list_releases().await? {
+        if release.draft {
+            continue;
+        }
+        let version = match tag_format.version_of(&release.tag_name) {
+            Some(v) => v,
+            // other crates in the same repository
+            None if package.tag_format.is_some() => continue,
+            None => normalize_version(&release.tag_name).to_string(),
+        };
+        releases.push((version, release));
+    }
+
+    let templates = match (package.binstall.as_ref(), releases.first()) {
+        (Some(binstall), Some((_, latest))) => {
+            match binstall_templates(binstall, package, latest, rust_abi, loader).await {
+                Ok(t) => Some(t),
+                Err(e) => {
+                    debug!("failed to read binstall metadata from source: {}", e);
+                    let binstall = Binstall {
+                        from_source: false,
+                        ..binstall.clone()
+                    };
+                    binstall_templates(&binstall, package, latest, rust_abi, loader)
+                        .await
+                        .ok()
+                }
+            }
+        }
+        _ => None,
+    };
+
+    let versions = stream::iter(releases)
+        .map(|(version, release)| {
+            let templates = templates.as_ref();
+            async move {
+                let has_asset =
+                    has_asset(provider, package, &release, &version, templates, rust_abi).await;
+                VersionInfo {
+                    installed: installed.contains(&version),
+                    current: current == Some(version.as_str()),
+                    version,
+                    tag_name: release.tag_name,
+                    published_at: release.published_at,
+                    prerelease: release.prerelease,
+                    has_asset,
+                }
+            }
+        })
+        .buffered(CONCURRENCY)
+        .collect()
+        .await;
+    Ok(versions)
+}
+
+/// an asset from binstall `pkg-url` may not be in the release, it's checked by a request.
+async fn has_asset<P: ReleaseProvider>(
+    provider: &P,
+    package: &Package,
+    release: &RemoteRelease,
+    version: &str,
+    templates: Option<&Templates>,
+    rust_abi: &str,
+) -> bool {
+    let assets = match provider.assets(release).await {
+        Ok(a) => a,
+        Err(e) => {
+            debug!("failed to find assets of {}: {}", release.tag_name, e);
+            return false;
+        }
+    };
+    let selected = match select_release_assets(
+        provider,
+        package,
+        assets.clone(),
+        version,
+        templates,
+        &package.remote_file_prefix,
+        rust_abi,
+    ) {
+        Ok(s) => s,
+        Err(e) => {
+            debug!("failed to find assets of {}: {}", release.tag_name, e);
+            return false;
+        }
+    };
+    match selected.first() {
+        None => false,
+        Some(a) if !assets.iter().any(|r| r.download_url == a.download_url) => {
+            match http::exists(&a.download_url).await {
+                Ok(exists) => exists,
+                Err(e) => {
+                    debug!("failed to check {}: {}", a.download_url, e);
+                    false
+                }
+            }
+        }
+        Some(_) => true,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::action::installer::{install::AppAsset, provider::MockReleaseProvider};
+    use mockito::Server;
+    use std::env::consts::{ARCH, OS};
+
+    fn release(tag_name: &str, prerelease: bool, draft: bool, asset: &str) -> RemoteRelease {
+        RemoteRelease {
+            tag_name: tag_name.to_string(),
+            prerelease,
+            draft,
+            published_at: Some("2024-01-01T00:00:00Z".to_string()),
+            assets: vec![AppAsset {
+                name: asset.to_string(),
+                download_url: format!("https://example.com/{}", asset),
+                bin_path: None,
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_collect_versions() {
+        let asset = format!("tool-{}-{}-gnu.tar.gz", ARCH, OS);
+        let releases = vec![
+            release("v2.0.0", false, true, &asset),
+            release("v1.1.0-rc.1", true, false, &asset),
+            release("v1.0.0", false, false, &asset),
+            release("v0.9.0", false, false, "tool-unknown.tar.gz"),
+        ];
+        let mut provider = MockReleaseProvider::new();
+        provider
+            .expect_list_releases()
+            .returning(move || Ok(releases.clone()));
+        provider.expect_assets().returning(|r| Ok(r.assets.clone()));
+        provider.expect_is_platform_specific().return_const(false);
+        let package: Package = toml::from_str(
+            r#"
+            name = "tool"
+            url = "https://example.com/tool"
+            bin_name = "tool"
+            "#,
+        )
+        .unwrap();
+
+        let installed = vec!["1.0.0".to_string(), "0.9.0".to_string()];
+        let dir = tempfile::tempdir().unwrap();
+        let app_dir = AppDir::from_home(dir.path().join("home")).unwrap();
+        let metadata = SourceMetadata::default();
+        let pb = ProgressBar::hidden();
+        let loader = SourceLoader {
+            app_dir: &app_dir,
+            tmp_dir: dir.path(),
+            metadata: &metadata,
+            pb: &pb,
+        };
+        let versions = collect_versions(
+            &provider,
+            &package,
+            "gnu",
+            &installed,
+            Some("1.0.0"),
+            &loader,
+        )
+        .await
+        .unwrap();
+        let summary: Vec<_> = versions
+            .iter()
+            .map(|v| {
+                (
+                    v.version.as_str(),
+                    v.prerelease,
+                    v.has_asset,
+                    v.installed,
+                    v.current,
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                ("1.1.0-rc.1", true, true, false, false),
+                ("1.0.0", false, true, true, true),
+                ("0.9.0", false, false, true, false),
+            ],
+            summary
+        );
+        let json = serde_json::to_value(&versions[1]).unwrap();
+        assert_eq!("v1.0.0", json["tag_name"]);
+        assert_eq!("2024-01-01T00:00:00Z", json["published_at"]);
+    }
+
+    #[tokio::test]
+    async fn test_binstall_url_outside_release() {
+        let mut server = Server::new_async().await;
+        let found = server
+            .mock("HEAD", "/v1.0.0/tool.tar.gz")
+            .create_async()
+            .await;
+        let missing = server
+            .mock("HEAD", "/v0.9.0/tool.tar.gz")
+            .with_status(404)
+            .create_async()
+            .await;
+        let releases = vec![
+            release("v1.0.0", false, false, "checksums.txt"),
+            release("v0.9.0", false, false, "checksums.txt"),
+        ];
+        let mut provider = MockReleaseProvider::new();
+        provider
+            .expect_list_releases()
+            .returning(move || Ok(releases.clone()));
+        provider.expect_assets().returning(|r| Ok(r.assets.clone()));
+        provider.expect_is_platform_specific().return_const(false);
+        let package: Package = toml::from_str(&format!(
+            r#"
+            name = "tool"
+            url = "https://example.com/tool"
+            bin_name = "tool"
+            [binstall]
+            pkg_url = "{}/v{{ version }}/tool.tar.gz"
+            "#,
+            server.url()
+        ))
+        .unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let app_dir = AppDir::from_home(dir.path().join("home")).unwrap();
+        let metadata = SourceMetadata::default();
+        let pb = ProgressBar::hidden();
+        let loader = SourceLoader {
+            app_dir: &app_dir,
+            tmp_dir: dir.path(),
+            metadata: &metadata,
+            pb: &pb,
+        };
+        let versions = collect_versions(
+            &provider,
+            &package,
+            "gnu",
+            &[],
+            None,
+            &loader,
+        )
+        .await
+        .unwrap();
+        let has_asset: Vec<_> = versions
+            .iter()
+            .map(|v| (v.version.as_str(), v.has_asset))
+            .collect();
+        assert_eq!(vec![("1.0.0", true), ("0.9.0", false)], has_asset);
+        found.assert_async().await;
+        missing.assert_async().await;
+    }
+}
@@ -1,7 +1,18 @@
 use colored::Colorize;
 
-use super::helper::get_installed_packages;
-use crate::tool::{fs::AppDir, logger::error, kaleido};
+use super::helper::{get_installed_packages, installed_versions};
+use crate::tool::{fs::AppDir, logger::error, kaleido, state::Pins};
+
+/// pinned packages, an empty list if pins cannot be loaded.
+fn load_pins(app_dir: &AppDir) -> Pins {
+    match Pins::load(app_dir) {
+        Ok(p) => p,
+        Err(e) => {
+            error!("Failed to load pins: {}", e);
+            Pins::default()
+        }
+    }
+}
 
 pub async fn list(app_dir: &AppDir) {
     let installed_packages = match get_installed_packages(app_dir).await {
@@ -12,12 +23,17 @@ pub async fn list(app_dir: &AppDir) {
         }
     };
 
+    let pins = load_pins(app_dir);
     for package in installed_packages {
-        println!(
+        print!(
             "* {} - {}",
             package.package.name.green(),
-            package.version.green()
+            package.display_version().green()
         );
+        if let Some(pin) = pins.get(&package.package.name) {
+            print!(" [{}]", pin.describe().yellow());
+        }
+        println!();
     }
 }
 
@@ -37,14 +53,18 @@ pub async fn list_all(app_dir: &AppDir) {
         }
     };
 
+    let pins = load_pins(app_dir);
     for package in kaleido.packages {
         match installed_packages.iter().find(|p| p.package.name == package.name) {
             Some(ip) => {
                 print!(
                     "* {} ({})",
                     package.name.green(),
-                    ip.version.green()
+                    ip.display_version().green()
                 );
+                if let Some(pin) = pins.get(&package.name) {
+                    print!(" [{}]", pin.describe().yellow());
+                }
             }
             None => {
                 print!("  {}", package.name);
@@ -61,3 +81,30 @@ pub async fn list_all(app_dir: &AppDir) {
         println!()
     }
 }
+
+/// list all installed versions of installed packages, the active one is marked.
+pub async fn list_versions(app_dir: &AppDir) {
+    let installed_packages = match get_installed_packages(app_dir).await {
+        Ok(p) => p,
+        Err(e) => {
+            error!("Failed to get installed packages: {}", e);
+            return;
+        }
+    };
+
+    let pins = load_pins(app_dir);
+    for package in installed_packages {
+        print!("{}", package.package.name.green());
+        if let Some(pin) = pins.get(&package.package.name) {
+            print!(" [{}]", pin.describe().yellow());
+        }
+        println!();
+        for version in installed_versions(app_dir, &package.package.name) {
+            if version == package.version {
+                println!("  * {}", version.green());
+            } else {
+                println!("    {}", version);
+            }
+        }
+    }
+}
@@ -2,8 +2,14 @@ pub mod search;
 pub mod installer;
 pub mod config;
 pub mod list;
+pub mod pin;
+pub mod prune;
 pub mod uninstall;
+pub mod update;
+pub mod switch;
 pub mod upgrade;
 pub mod bindle;
+pub mod cache;
 
-mod helper;
\ No newline at end of file
+mod helper;
+pub use helper::migrate_receipts;
@@ -0,0 +1,171 @@
+use colored::Colorize;
+
+use super::helper::{get_installed_packages, parse_package_spec};
+use super::installer::{normalize_version, InstallRequest};
+use crate::tool::{
+    fs::AppDir,
+    kaleido,
+    logger::error,
+    state::{Pin, Pins},
+};
+
+/// pin a package, the version is 'name@version', or the installed version if it's not given.
+pub async fn pin(package: String, app_dir: &AppDir) {
+    let (name, version) = parse_package_spec(&package);
+    match kaleido::load_config(app_dir.get_home_dir()) {
+        Ok(k) => {
+            if !k.packages.iter().any(|p| p.name == name) {
+                println!(
+                    "{}",
+                    format!("cannot find package with name {}", name).red()
+                );
+                return;
+            }
+        }
+        Err(e) => {
+            error!("Failed to load metadata: {}", e);
+            return;
+        }
+    }
+    let version = match version {
+        Some(v) => Some(normalize_version(&v).to_string()),
+        None => match get_installed_packages(app_dir).await {
+            Ok(packages) => packages
+                .into_iter()
+                .find(|p| p.package.name == name)
+                .map(|p| normalize_version(&p.version).to_string()),
+            Err(e) => {
+                error!("Failed to get installed packages: {}", e);
+                return;
+            }
+        },
+    };
+
+    let mut pins = match Pins::load(app_dir) {
+        Ok(p) => p,
+        Err(e) => {
+            error!("Failed to load pins: {}", e);
+            return;
+        }
+    };
+    let pin = Pin { version };
+    let description = pin.describe();
+    pins.packages.insert(name.clone(), pin);
+    match pins.save(app_dir) {
+        Ok(_) => println!("{} is {}", name.green(), description),
+        Err(e) => error!("Failed to save pins: {}", e),
+    }
+}
+
+pub async fn unpin(name: String, app_dir: &AppDir) {
+    let mut pins = match Pins::load(app_dir) {
+        Ok(p) => p,
+        Err(e) => {
+            error!("Failed to load pins: {}", e);
+            return;
+        }
+    };
+    if pins.packages.remove(&name).is_none() {
+        println!("{} is not pinned", name);
+        return;
+    }
+    match pins.save(app_dir) {
+        Ok(_) => println!("{} is unpinned", name.green()),
+        Err(e) => error!("Failed to save pins: {}", e),
+    }
+}
+
+/// remove pinned packages from the requests, nothing is removed if force is true.
+/// a request of the pinned version is kept.
+pub fn skip_pinned(
+    requests: Vec<InstallRequest>,
+    app_dir: &AppDir,
+    force: bool,
+) -> Vec<InstallRequest> {
+    if force {
+        return requests;
+    }
+    let pins = match Pins::load(app_dir) {
+        Ok(p) => p,
+        Err(e) => {
+            error!("Failed to load pins: {}", e);
+            return requests;
+        }
+    };
+    requests
+        .into_iter()
+        .filter(|r| match pins.get(&r.name) {
+            Some(pin) if is_pinned_version(pin, r.version.as_deref()) => true,
+            Some(pin) => {
+                println!(
+                    "the package {} is {}, skip it, use --force to update it",
+                    r.name,
+                    pin.describe()
+                );
+                false
+            }
+            None => true,
+        })
+        .collect()
+}
+
+/// true if the version is the one the package is pinned at, 'v1.0.0' and '1.0.0' are the same.
+fn is_pinned_version(pin: &Pin, version: Option<&str>) -> bool {
+    match (&pin.version, version) {
+        (Some(pinned), Some(v)) => normalize_version(pinned) == normalize_version(v),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn request(name: &str, version: Option<&str>) -> InstallRequest {
+        InstallRequest {
+            name: name.to_string(),
+            alias: None,
+            version: version.map(|v| v.to_string()),
+            prerelease: false,
+        }
+    }
+
+    #[test]
+    fn test_skip_pinned() {
+        let dir = tempfile::tempdir().unwrap();
+        let app_dir = AppDir::from_home(dir.path().join("home")).unwrap();
+        let mut pins = Pins::default();
+        pins.packages.insert(
+            "eza".to_string(),
+            Pin {
+                version: Some("v0.18.0".to_string()),
+            },
+        );
+        pins.packages
+            .insert("fd".to_string(), Pin { version: None });
+        pins.save(&app_dir).unwrap();
+
+        let requests = vec![
+            request("eza", None),
+            request("eza", Some("0.18.0")),
+            request("eza", Some("0.19.0")),
+            request("fd", Some("9.0.0")),
+            request("dust", None),
+        ];
+        let kept: Vec<_> = skip_pinned(requests, &app_dir, false)
+            .into_iter()
+            .map(|r| (r.name, r.version))
+            .collect();
+        assert_eq!(
+            vec![
+                ("eza".to_string(), Some("0.18.0".to_string())),
+                ("dust".to_string(), None),
+            ],
+            kept
+        );
+        assert_eq!(
+            5,
+            skip_pinned(vec![request("fd", None); 5], &app_dir, true).len()
+        );
+    }
+}
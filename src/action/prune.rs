@@ -0,0 +1,231 @@
+use colored::Colorize;
+use indicatif::HumanBytes;
+use std::collections::HashSet;
+use std::fs::{read_dir, remove_dir_all};
+
+use super::helper::{get_installed_packages, installed_versions};
+use super::installer::normalize_version;
+use crate::tool::{
+    fs::{dir_size, AppDir},
+    logger::error,
+    state::{PackageReceipts, Pin, Pins},
+};
+
+/// remove versions which are not active, not pinned, not linked by aliases, not needed by rollback
+/// and beyond the newest `keep` ones.
+pub async fn prune(app_dir: &AppDir, keep: usize, dry_run: bool) {
+    // receipts are migrated from links if needed
+    if let Err(e) = get_installed_packages(app_dir).await {
+        error!("Failed to get installed packages: {}", e);
+        return;
+    }
+    let pins = match Pins::load(app_dir) {
+        Ok(p) => p,
+        Err(e) => {
+            error!("Failed to load pins: {}", e);
+            return;
+        }
+    };
+    let names: Vec<String> = match read_dir(app_dir.get_packages_dir()) {
+        Ok(entries) => entries
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().is_dir())
+            .filter_map(|e| e.file_name().to_str().map(|n| n.to_string()))
+            .collect(),
+        Err(e) => {
+            error!("Failed to list packages: {}", e);
+            return;
+        }
+    };
+
+    let mut freed = 0;
+    let mut count = 0;
+    for name in names {
+        let mut receipts = match PackageReceipts::load(app_dir, &name) {
+            Ok(r) => r,
+            Err(e) => {
+                error!("Failed to load the receipt of {}: {}", name, e);
+                continue;
+            }
+        };
+        // the active version is unknown
+        let current = match &receipts.current {
+            Some(c) => c.clone(),
+            None => continue,
+        };
+        let protected = protected_versions(&receipts, &current, pins.get(&name));
+
+        let versions = installed_versions(app_dir, &name);
+        for version in versions_to_prune(&versions, keep, &protected) {
+            let version_dir = app_dir.get_packages_dir().join(&name).join(&version);
+            let size = dir_size(&version_dir).unwrap_or_default();
+            if dry_run {
+                println!("would remove {} {} ({})", name, version, HumanBytes(size));
+            } else {
+                if let Err(e) = remove_dir_all(&version_dir) {
+                    error!("failed to remove dir({:?}), error: {}", version_dir, e);
+                    continue;
+                }
+                println!("removed {} {} ({})", name, version, HumanBytes(size));
+                forget_version(&mut receipts, &version);
+            }
+            freed += size;
+            count += 1;
+        }
+        if !dry_run {
+            if let Err(e) = receipts.save(app_dir) {
+                error!("failed to save the receipt of {}: {}", name, e);
+            }
+        }
+    }
+
+    let summary = if dry_run {
+        format!("{} versions, {} would be freed", count, HumanBytes(freed))
+    } else {
+        format!("{} versions removed, {} freed", count, HumanBytes(freed))
+    };
+    println!("{}", summary.green());
+}
+
+/// the current version, versions linked by aliases, the pinned version,
+/// and versions which rollback goes back to.
+fn protected_versions(
+    receipts: &PackageReceipts,
+    current: &str,
+    pin: Option<&Pin>,
+) -> HashSet<String> {
+    let mut protected: HashSet<String> = receipts.alias_map().into_values().collect();
+    protected.insert(current.to_string());
+    if let Some(v) = pin.and_then(|p| p.version.as_ref()) {
+        protected.insert(v.clone());
+    }
+    if let Some(previous) = receipts.history.last() {
+        protected.insert(previous.version.clone());
+        protected.extend(previous.aliases.values().cloned());
+    }
+    // a pin may have the 'v' prefix of a tag, but a directory doesn't
+    protected
+        .iter()
+        .map(|v| normalize_version(v).to_string())
+        .collect()
+}
+
+/// versions beyond the newest `keep` ones which are not protected, versions are sorted from the newest.
+/// protected versions are normalized, e.g. '1.0.0' also protects the directory 'v1.0.0'.
+fn versions_to_prune(versions: &[String], keep: usize, protected: &HashSet<String>) -> Vec<String> {
+    versions
+        .iter()
+        .skip(keep)
+        .filter(|v| !protected.contains(normalize_version(v)))
+        .cloned()
+        .collect()
+}
+
+/// remove a pruned version from receipts, a directory could be named by the tag, e.g. 'v1.0.0'.
+fn forget_version(receipts: &mut PackageReceipts, version: &str) {
+    let removed = normalize_version(version);
+    receipts
+        .versions
+        .retain(|r| normalize_version(&r.version) != removed);
+    receipts
+        .history
+        .retain(|a| normalize_version(&a.version) != removed);
+    for activation in receipts.history.iter_mut() {
+        activation
+            .aliases
+            .retain(|_, v| normalize_version(v) != removed);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_versions_to_prune() {
+        let versions: Vec<String> = ["4.0.0", "3.0.0", "2.0.0", "1.0.0"]
+            .iter()
+            .map(|v| v.to_string())
+            .collect();
+        let protected: HashSet<String> = ["1.0.0".to_string()].into_iter().collect();
+        assert_eq!(vec!["2.0.0"], versions_to_prune(&versions, 2, &protected));
+        assert_eq!(
+            vec!["3.0.0", "2.0.0"],
+            versions_to_prune(&versions, 1, &protected)
+        );
+        assert!(versions_to_prune(&versions, 4, &HashSet::new()).is_empty());
+        assert_eq!(4, versions_to_prune(&versions, 0, &HashSet::new()).len());
+
+        // directories created from tags keep the 'v' prefix
+        let versions: Vec<String> = ["v2.0.0", "v1.0.0"].iter().map(|v| v.to_string()).collect();
+        assert_eq!(vec!["v2.0.0"], versions_to_prune(&versions, 0, &protected));
+    }
+
+    #[test]
+    fn test_protected_versions() {
+        let mut receipts: PackageReceipts = toml::from_str(
+            r#"
+            name = "tool"
+            current = "3.0.0"
+            [[versions]]
+            version = "3.0.0"
+            bin_name = "tool"
+            aliases = ["tl"]
+            installed_at = 0
+            [[history]]
+            version = "1.0.0"
+            [[history]]
+            version = "2.0.0"
+            aliases = { old = "0.9.0" }
+            "#,
+        )
+        .unwrap();
+        let pin = Pin {
+            version: Some("v0.5.0".to_string()),
+        };
+        let mut protected: Vec<_> = protected_versions(&receipts, "3.0.0", Some(&pin))
+            .into_iter()
+            .collect();
+        protected.sort();
+        assert_eq!(vec!["0.5.0", "0.9.0", "2.0.0", "3.0.0"], protected);
+
+        receipts.history.clear();
+        assert_eq!(1, protected_versions(&receipts, "3.0.0", None).len());
+    }
+
+    #[test]
+    fn test_forget_tag_version() {
+        let mut receipts: PackageReceipts = toml::from_str(
+            r#"
+            name = "tool"
+            current = "2.0.0"
+            [[versions]]
+            version = "1.0.0"
+            bin_name = "tool"
+            installed_at = 0
+            [[versions]]
+            version = "2.0.0"
+            bin_name = "tool"
+            installed_at = 0
+            [[history]]
+            version = "1.0.0"
+            [[history]]
+            version = "2.0.0"
+            aliases = { old = "1.0.0", new = "2.0.0" }
+            "#,
+        )
+        .unwrap();
+        forget_version(&mut receipts, "v1.0.0");
+        let versions: Vec<_> = receipts
+            .versions
+            .iter()
+            .map(|r| r.version.as_str())
+            .collect();
+        assert_eq!(vec!["2.0.0"], versions);
+        assert_eq!(1, receipts.history.len());
+        assert_eq!(
+            vec!["new"],
+            receipts.history[0].aliases.keys().collect::<Vec<_>>()
+        );
+    }
+}
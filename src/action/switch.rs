@@ -0,0 +1,466 @@
+use colored::Colorize;
+use std::env::consts::EXE_EXTENSION;
+use std::path::PathBuf;
+
+use super::helper::{installed_versions, parse_package_spec};
+use super::installer::{normalize_version, platform_keys, InstallError, TagFormat, Transaction};
+use crate::tool::{
+    fs::AppDir,
+    kaleido,
+    logger::error,
+    state::{unix_now, PackageReceipts, Receipt},
+};
+
+/// link the bin file and aliases to an installed version, e.g. 'eza@0.18.0' or a tag like 'eza@v0.18.0'.
+pub async fn use_version(spec: String, app_dir: &AppDir, rust_abi: &str) {
+    let (name, version) = match parse_package_spec(&spec) {
+        (n, Some(v)) => (n, v),
+        (n, None) => {
+            println!(
+                "{}",
+                format!("a version is required, e.g. {}@1.0.0", n).red()
+            );
+            return;
+        }
+    };
+    let package = match kaleido::load_config(app_dir.get_home_dir()) {
+        Ok(k) => match k.packages.into_iter().find(|p| p.name == name) {
+            Some(p) => p,
+            None => {
+                println!(
+                    "{}",
+                    format!("cannot find package with name {}", name).red()
+                );
+                return;
+            }
+        },
+        Err(e) => {
+            error!("Failed to load metadata: {}", e);
+            return;
+        }
+    };
+    let package = package.for_platform(&platform_keys(rust_abi));
+    // a tag is accepted as well as a version
+    let version = TagFormat::from_package(&package)
+        .version_of(&version)
+        .unwrap_or_else(|| normalize_version(&version).to_string());
+    let version_dir = match installed_versions(app_dir, &name)
+        .into_iter()
+        .find(|v| normalize_version(v) == version)
+    {
+        Some(v) => v,
+        None => {
+            println!(
+                "{}",
+                format!("the version {} of {} is not installed", version, name).red()
+            );
+            return;
+        }
+    };
+
+    let mut receipts = match PackageReceipts::load(app_dir, &name) {
+        Ok(r) => r,
+        Err(e) => {
+            error!("Failed to load the receipt of {}: {}", name, e);
+            return;
+        }
+    };
+    let bin_name = receipts
+        .versions
+        .iter()
+        .find(|r| r.version == version_dir)
+        .map(|r| r.bin_name.clone())
+        .unwrap_or(package.bin_name);
+    let bin_file = version_bin_file(app_dir, &name, &version_dir, &bin_name);
+    if !bin_file.is_file() {
+        println!(
+            "{}",
+            format!("cannot find {:?} of {}", bin_file, name).red()
+        );
+        return;
+    }
+
+    let mut links = vec![(
+        link_file(app_dir.get_bin_dir(), &bin_name),
+        bin_file.clone(),
+    )];
+    for alias in receipts.alias_map().keys() {
+        links.push((link_file(app_dir.get_alias_dir(), alias), bin_file.clone()));
+    }
+    let stale = stale_bin_links(app_dir, &receipts, &bin_name);
+    if let Err(e) = switch_links(&links, &stale) {
+        println!("{}", format!("failed to link {}: {}", name, e).red());
+        return;
+    }
+
+    // versions installed before receipts only have directories
+    if !receipts.versions.iter().any(|r| r.version == version_dir) {
+        receipts.insert(Receipt {
+            version: version_dir.clone(),
+            bin_name,
+            source_url: None,
+            asset_name: None,
+            sha256: None,
+            aliases: vec![],
+            installed_at: unix_now(),
+            build: None,
+        });
+    }
+    receipts.activate(&version_dir);
+    receipts.move_aliases(&version_dir);
+    if let Err(e) = receipts.save(app_dir) {
+        error!("failed to save the receipt of {}: {}", name, e);
+    }
+    println!("{} is using version {}", name.green(), version_dir.green());
+}
+
+/// activate the previous version of a package, aliases are linked as they were.
+pub async fn rollback(name: String, app_dir: &AppDir) {
+    let mut receipts = match PackageReceipts::load(app_dir, &name) {
+        Ok(r) => r,
+        Err(e) => {
+            error!("Failed to load the receipt of {}: {}", name, e);
+            return;
+        }
+    };
+    let previous = match receipts.history.last() {
+        Some(a) => a.clone(),
+        None => {
+            println!("{}", format!("no previous version of {}", name).red());
+            return;
+        }
+    };
+    if !app_dir
+        .get_packages_dir()
+        .join(&name)
+        .join(&previous.version)
+        .is_dir()
+    {
+        println!(
+            "{}",
+            format!(
+                "the version {} of {} has been removed",
+                previous.version, name
+            )
+            .red()
+        );
+        return;
+    }
+    let bin_name = match receipts
+        .versions
+        .iter()
+        .find(|r| r.version == previous.version)
+        .or(receipts.current_receipt())
+    {
+        Some(r) => r.bin_name.clone(),
+        None => {
+            println!("{}", format!("no receipt of {}", name).red());
+            return;
+        }
+    };
+
+    let mut links = vec![(
+        link_file(app_dir.get_bin_dir(), &bin_name),
+        version_bin_file(app_dir, &name, &previous.version, &bin_name),
+    )];
+    let mut aliases = previous.aliases.clone();
+    aliases.retain(|alias, version| {
+        let bin_file = version_bin_file(app_dir, &name, version, &bin_name);
+        if !bin_file.is_file() {
+            println!(
+                "{}",
+                format!(
+                    "the version {} linked by {} has been removed, skip the alias",
+                    version, alias
+                )
+                .yellow()
+            );
+            return false;
+        }
+        links.push((link_file(app_dir.get_alias_dir(), alias), bin_file));
+        true
+    });
+    // aliases which are not in the previous activation are removed
+    let mut stale = stale_bin_links(app_dir, &receipts, &bin_name);
+    stale.extend(
+        receipts
+            .alias_map()
+            .keys()
+            .filter(|a| !aliases.contains_key(*a))
+            .map(|a| link_file(app_dir.get_alias_dir(), a)),
+    );
+    if let Err(e) = switch_links(&links, &stale) {
+        println!("{}", format!("failed to roll back {}: {}", name, e).red());
+        return;
+    }
+
+    // the version left is saved in history, so another rollback comes back to it
+    receipts.history.pop();
+    receipts.activate(&previous.version);
+    receipts.restore_aliases(&aliases);
+    if let Err(e) = receipts.save(app_dir) {
+        error!("failed to save the receipt of {}: {}", name, e);
+    }
+    println!(
+        "{} is rolled back to version {}",
+        name.green(),
+        previous.version.green()
+    );
+}
+
+fn version_bin_file(app_dir: &AppDir, name: &str, version: &str, bin_name: &str) -> PathBuf {
+    let mut bin_file = app_dir
+        .get_packages_dir()
+        .join(name)
+        .join(version)
+        .join(bin_name);
+    bin_file.set_extension(EXE_EXTENSION);
+    bin_file
+}
+
+/// e.g. 'bin/tool' for 'tool', or 'bin/tool.exe' on windows.
+fn link_file(dir: &std::path::Path, name: &str) -> PathBuf {
+    let mut link = dir.join(name);
+    link.set_extension(EXE_EXTENSION);
+    link
+}
+
+/// the bin link of the current version if its bin name is different.
+fn stale_bin_links(app_dir: &AppDir, receipts: &PackageReceipts, bin_name: &str) -> Vec<PathBuf> {
+    receipts
+        .current_receipt()
+        .filter(|r| r.bin_name != bin_name)
+        .map(|r| link_file(app_dir.get_bin_dir(), &r.bin_name))
+        .into_iter()
+        .collect()
+}
+
+/// point links to their targets and remove stale links, nothing is changed if any of them fails.
+fn switch_links(links: &[(PathBuf, PathBuf)], stale: &[PathBuf]) -> Result<(), InstallError> {
+    let mut transaction = Transaction::new();
+    for link in stale {
+        if link.symlink_metadata().is_ok() {
+            transaction.remove_link(link)?;
+        }
+    }
+    for (link, target) in links {
+        transaction.swap_link(link, target)?;
+    }
+    transaction.commit();
+    Ok(())
+}
+
+#[cfg(all(test, not(target_os = "windows")))]
+mod test {
+    use super::*;
+    use crate::action::helper::get_installed_packages;
+    use crate::tool::fs::make_link;
+    use std::fs::{create_dir_all, read_link, write};
+
+    #[tokio::test]
+    async fn test_use_and_rollback() {
+        let dir = tempfile::tempdir().unwrap();
+        let app_dir = AppDir::from_home(dir.path().join("home")).unwrap();
+        write(
+            app_dir.get_home_dir().join(kaleido::KALEIDO_SYS_FILE),
+            r#"
+            bindles = []
+            [[packages]]
+            name = "tool"
+            url = "https://example.com/tool"
+            bin_name = "tool"
+            "#,
+        )
+        .unwrap();
+        for v in ["1.0.0", "2.0.0"] {
+            let version_dir = app_dir.get_packages_dir().join("tool").join(v);
+            create_dir_all(&version_dir).unwrap();
+            write(version_dir.join("tool"), "").unwrap();
+        }
+        let new_bin = app_dir.get_packages_dir().join("tool/2.0.0/tool");
+        make_link(&app_dir.get_bin_dir().join("tool"), &new_bin).unwrap();
+        make_link(&app_dir.get_alias_dir().join("tl"), &new_bin).unwrap();
+        // receipts are migrated from links
+        assert_eq!(1, get_installed_packages(&app_dir).await.unwrap().len());
+
+        use_version("tool@v1.0.0".to_string(), &app_dir, "gnu").await;
+
+        let old_bin = app_dir.get_packages_dir().join("tool/1.0.0/tool");
+        assert_eq!(
+            old_bin,
+            read_link(app_dir.get_bin_dir().join("tool")).unwrap()
+        );
+        assert_eq!(
+            old_bin,
+            read_link(app_dir.get_alias_dir().join("tl")).unwrap()
+        );
+        let receipts = PackageReceipts::load(&app_dir, "tool").unwrap();
+        assert_eq!(Some("1.0.0"), receipts.current.as_deref());
+        assert_eq!(vec!["tl"], receipts.current_receipt().unwrap().aliases);
+
+        rollback("tool".to_string(), &app_dir).await;
+
+        assert_eq!(
+            new_bin,
+            read_link(app_dir.get_bin_dir().join("tool")).unwrap()
+        );
+        assert_eq!(
+            new_bin,
+            read_link(app_dir.get_alias_dir().join("tl")).unwrap()
+        );
+        let receipts = PackageReceipts::load(&app_dir, "tool").unwrap();
+        assert_eq!(Some("2.0.0"), receipts.current.as_deref());
+        assert_eq!(
+            vec!["1.0.0"],
+            receipts
+                .history
+                .iter()
+                .map(|a| a.version.as_str())
+                .collect::<Vec<_>>()
+        );
+
+        // rollback twice comes back to where it started
+        rollback("tool".to_string(), &app_dir).await;
+
+        assert_eq!(
+            old_bin,
+            read_link(app_dir.get_alias_dir().join("tl")).unwrap()
+        );
+        let receipts = PackageReceipts::load(&app_dir, "tool").unwrap();
+        assert_eq!(Some("1.0.0"), receipts.current.as_deref());
+        assert_eq!("2.0.0", receipts.history.last().unwrap().version);
+    }
+
+    #[tokio::test]
+    async fn test_rollback_to_other_bin_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let app_dir = AppDir::from_home(dir.path().join("home")).unwrap();
+        let mut receipts = PackageReceipts {
+            name: "tool".to_string(),
+            ..Default::default()
+        };
+        for (v, bin_name) in [("1.0.0", "tool-old"), ("2.0.0", "tool")] {
+            let version_dir = app_dir.get_packages_dir().join("tool").join(v);
+            create_dir_all(&version_dir).unwrap();
+            write(version_dir.join(bin_name), "").unwrap();
+            make_link(
+                &app_dir.get_bin_dir().join(bin_name),
+                &version_dir.join(bin_name),
+            )
+            .unwrap();
+            receipts.record(Receipt {
+                version: v.to_string(),
+                bin_name: bin_name.to_string(),
+                source_url: None,
+                asset_name: None,
+                sha256: None,
+                aliases: vec![],
+                installed_at: unix_now(),
+                build: None,
+            });
+        }
+        receipts.save(&app_dir).unwrap();
+
+        rollback("tool".to_string(), &app_dir).await;
+
+        assert!(app_dir
+            .get_bin_dir()
+            .join("tool")
+            .symlink_metadata()
+            .is_err());
+        assert_eq!(
+            app_dir.get_packages_dir().join("tool/1.0.0/tool-old"),
+            read_link(app_dir.get_bin_dir().join("tool-old")).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_use_tag_with_platform_bin_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let app_dir = AppDir::from_home(dir.path().join("home")).unwrap();
+        write(
+            app_dir.get_home_dir().join(kaleido::KALEIDO_SYS_FILE),
+            format!(
+                r#"
+                bindles = []
+                [[packages]]
+                name = "tool"
+                url = "https://example.com/tool"
+                bin_name = "tool"
+                tag_format = "release-{{version}}"
+                [packages.platforms.{}]
+                bin_name = "tool-cli"
+                "#,
+                std::env::consts::OS
+            ),
+        )
+        .unwrap();
+        let version_dir = app_dir.get_packages_dir().join("tool").join("1.2.0");
+        create_dir_all(&version_dir).unwrap();
+        write(version_dir.join("tool-cli"), "").unwrap();
+
+        use_version("tool@release-1.2.0".to_string(), &app_dir, "gnu").await;
+
+        assert_eq!(
+            version_dir.join("tool-cli"),
+            read_link(app_dir.get_bin_dir().join("tool-cli")).unwrap()
+        );
+        let receipts = PackageReceipts::load(&app_dir, "tool").unwrap();
+        assert_eq!(Some("1.2.0"), receipts.current.as_deref());
+        assert_eq!("tool-cli", receipts.current_receipt().unwrap().bin_name);
+    }
+
+    #[tokio::test]
+    async fn test_rollback_without_alias_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let app_dir = AppDir::from_home(dir.path().join("home")).unwrap();
+        for v in ["1.0.0", "2.0.0"] {
+            let version_dir = app_dir.get_packages_dir().join("tool").join(v);
+            create_dir_all(&version_dir).unwrap();
+            write(version_dir.join("tool"), "").unwrap();
+        }
+        let mut receipts = PackageReceipts {
+            name: "tool".to_string(),
+            ..Default::default()
+        };
+        for v in ["1.0.0", "2.0.0"] {
+            receipts.record(Receipt {
+                version: v.to_string(),
+                bin_name: "tool".to_string(),
+                source_url: None,
+                asset_name: None,
+                sha256: None,
+                aliases: vec![],
+                installed_at: unix_now(),
+                build: None,
+            });
+        }
+        // the previous activation had an alias to a version which is pruned
+        let previous = receipts.history.last_mut().unwrap();
+        previous
+            .aliases
+            .insert("old".to_string(), "0.9.0".to_string());
+        previous
+            .aliases
+            .insert("tl".to_string(), "1.0.0".to_string());
+        receipts.save(&app_dir).unwrap();
+
+        rollback("tool".to_string(), &app_dir).await;
+
+        assert!(app_dir
+            .get_alias_dir()
+            .join("old")
+            .symlink_metadata()
+            .is_err());
+        assert_eq!(
+            app_dir.get_packages_dir().join("tool/1.0.0/tool"),
+            read_link(app_dir.get_alias_dir().join("tl")).unwrap()
+        );
+        let receipts = PackageReceipts::load(&app_dir, "tool").unwrap();
+        assert_eq!(Some("1.0.0"), receipts.current.as_deref());
+        assert_eq!(
+            vec!["tl"],
+            receipts.alias_map().into_keys().collect::<Vec<_>>()
+        );
+    }
+}
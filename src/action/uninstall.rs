@@ -1,24 +1,87 @@
-use std::{fs::remove_dir_all, path::PathBuf};
+use std::{env::consts::EXE_EXTENSION, fs::remove_dir_all, path::PathBuf};
 
 use tokio::fs::read_dir;
 
 use crate::tool::{
     fs::{remove_link, AppDir},
     logger::error,
+    state::{PackageReceipts, Pins},
 };
 
 pub async fn uninstall(package_names: Vec<String>, app_dir: &AppDir) {
-    let package_paths: Vec<PathBuf> = package_names
-        .iter()
-        .map(|n| app_dir.get_packages_dir().join(n))
+    let mut unrecorded = vec![];
+    for name in &package_names {
+        match PackageReceipts::load(app_dir, name) {
+            Ok(receipts) if !receipts.versions.is_empty() => {
+                remove_recorded_links(&receipts, app_dir);
+            }
+            Ok(_) => unrecorded.push(app_dir.get_packages_dir().join(name)),
+            Err(e) => {
+                error!("failed to load the receipt of {}: {}", name, e);
+                unrecorded.push(app_dir.get_packages_dir().join(name));
+            }
+        }
+    }
+    // packages without receipts are found by links
+    let unrecorded: Vec<PathBuf> = unrecorded
+        .into_iter()
         .filter(|p| p.exists() && p.is_dir())
         .collect();
-    remove_all_links(&package_paths, app_dir).await;
+    remove_all_links(&unrecorded, app_dir).await;
+
+    let package_paths = package_names
+        .iter()
+        .map(|n| app_dir.get_packages_dir().join(n))
+        .filter(|p| p.exists() && p.is_dir());
     for package_path in package_paths {
         if let Err(e) = remove_dir_all(&package_path) {
             error!("failed to remove dir({:?}), error: {}", package_path, e);
         }
     }
+    forget_packages(&package_names, app_dir);
+}
+
+/// remove pins and receipts of uninstalled packages, so nothing is left if they are installed again.
+fn forget_packages(package_names: &[String], app_dir: &AppDir) {
+    match Pins::load(app_dir) {
+        Ok(mut pins) => {
+            let count = pins.packages.len();
+            pins.packages.retain(|n, _| !package_names.contains(n));
+            if pins.packages.len() != count {
+                if let Err(e) = pins.save(app_dir) {
+                    error!("failed to save pins: {}", e);
+                }
+            }
+        }
+        Err(e) => error!("failed to load pins: {}", e),
+    }
+    for name in package_names {
+        if let Err(e) = PackageReceipts::remove(app_dir, name) {
+            error!("failed to remove the receipt of {}: {}", name, e);
+        }
+    }
+}
+
+/// remove the bin link of current version and all aliases in receipts.
+fn remove_recorded_links(receipts: &PackageReceipts, app_dir: &AppDir) {
+    let mut links = vec![];
+    if let Some(current) = receipts.current_receipt() {
+        links.push(app_dir.get_bin_dir().join(&current.bin_name));
+    }
+    for receipt in &receipts.versions {
+        for alias in &receipt.aliases {
+            links.push(app_dir.get_alias_dir().join(alias));
+        }
+    }
+    for mut link in links {
+        link.set_extension(EXE_EXTENSION);
+        // a link whose package is deleted doesn't exist
+        if link.symlink_metadata().is_ok() {
+            if let Err(e) = remove_link(&link) {
+                error!("failed to remove link({:?}), error: {}", link, e);
+            }
+        }
+    }
 }
 
 #[cfg(target_os = "windows")]
@@ -122,3 +185,49 @@ async fn remove_all_links(target_packages: &Vec<PathBuf>, app_dir: &AppDir) {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tool::state::{Pin, RECEIPTS_DIR};
+    use std::fs::create_dir_all;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_uninstall_forgets_pins_and_receipts() {
+        let dir = tempdir().unwrap();
+        let app_dir = AppDir::from_home(dir.path().join("home")).unwrap();
+        create_dir_all(app_dir.get_packages_dir().join("tool").join("1.0.0")).unwrap();
+        let receipts: PackageReceipts = toml::from_str(
+            r#"
+            name = "tool"
+            current = "1.0.0"
+            [[versions]]
+            version = "1.0.0"
+            bin_name = "tool"
+            installed_at = 0
+            "#,
+        )
+        .unwrap();
+        receipts.save(&app_dir).unwrap();
+        let mut pins = Pins::default();
+        pins.packages.insert("tool".to_string(), Pin::default());
+        pins.packages.insert("other".to_string(), Pin::default());
+        pins.save(&app_dir).unwrap();
+
+        uninstall(vec!["tool".to_string()], &app_dir).await;
+
+        assert!(!app_dir.get_packages_dir().join("tool").exists());
+        assert!(PackageReceipts::load(&app_dir, "tool")
+            .unwrap()
+            .versions
+            .is_empty());
+        assert!(!app_dir
+            .get_state_dir()
+            .join(RECEIPTS_DIR)
+            .join("tool.toml")
+            .exists());
+        let pins = Pins::load(&app_dir).unwrap();
+        assert_eq!(vec!["other"], pins.packages.keys().collect::<Vec<_>>());
+    }
+}
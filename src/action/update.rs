@@ -0,0 +1,128 @@
+use colored::Colorize;
+use std::env::consts::EXE_EXTENSION;
+
+use super::helper::{get_aliases, get_installed_packages};
+use super::installer::{
+    install, platform_keys, InstallRequest, InstallStatus, InstallerContext,
+};
+use crate::tool::{
+    fs::{swap_link, AppDir},
+    global_input::GlobalInput,
+    kaleido::{self, BindlePackage, Channel},
+    logger::error,
+    state::{PackageReceipts, Pins},
+};
+
+/// update every installed package to its latest matching version, pinned packages are skipped unless force.
+/// the version requirement and channel of a package in a bindle are respected.
+/// the statuses of the updated packages are returned, pinned ones are not in them.
+pub async fn update_all(
+    app_dir: &AppDir,
+    global_input: &mut GlobalInput<'_>,
+    context: &InstallerContext,
+    prerelease: bool,
+) -> Vec<InstallStatus> {
+    let installed_packages = match get_installed_packages(app_dir).await {
+        Ok(p) => p,
+        Err(e) => {
+            error!("Failed to get installed packages: {}", e);
+            return vec![];
+        }
+    };
+    let aliases = match get_aliases(app_dir).await {
+        Ok(a) => a,
+        Err(e) => {
+            error!("Failed to get aliases: {}", e);
+            vec![]
+        }
+    };
+
+    let pins = match Pins::load(app_dir) {
+        Ok(p) => p,
+        Err(e) => {
+            error!("Failed to load pins: {}", e);
+            Pins::default()
+        }
+    };
+
+    let bindle_packages: Vec<BindlePackage> = match kaleido::load_config(app_dir.get_home_dir()) {
+        Ok(k) => k.bindles.into_iter().flat_map(|b| b.packages).collect(),
+        Err(e) => {
+            error!("Failed to load metadata: {}", e);
+            vec![]
+        }
+    };
+
+    let mut updated = vec![];
+    let mut unchanged = vec![];
+    let mut pinned = vec![];
+    let mut failed = vec![];
+    let mut requests = vec![];
+    let mut to_update = vec![];
+    for ip in installed_packages {
+        let name = ip.package.name.clone();
+        if !context.force && pins.get(&name).is_some() {
+            println!("the package {} is pinned, skip it", name);
+            pinned.push(name);
+            continue;
+        }
+        let configured = bindle_packages.iter().find(|p| p.name == name);
+        requests.push(InstallRequest {
+            name: name.clone(),
+            alias: None,
+            version: configured.and_then(|p| p.version.clone()),
+            prerelease: prerelease
+                || configured.is_some_and(|p| p.channel == Some(Channel::Prerelease)),
+        });
+        to_update.push(ip);
+    }
+
+    let statuses = install(requests, app_dir, global_input, context).await;
+    for (ip, status) in to_update.into_iter().zip(statuses.iter().cloned()) {
+        let name = ip.package.name.clone();
+        match status {
+            InstallStatus::Installed(version) => {
+                // aliases still link to the old version
+                let package = ip.package.for_platform(&platform_keys(&context.rust_abi));
+                let mut bin_file = app_dir
+                    .get_packages_dir()
+                    .join(&name)
+                    .join(&version)
+                    .join(&package.bin_name);
+                bin_file.set_extension(EXE_EXTENSION);
+                for (alias, _) in aliases.iter().filter(|(_, a)| a.package.name == name) {
+                    let mut alias_file = app_dir.get_alias_dir().join(alias);
+                    alias_file.set_extension(EXE_EXTENSION);
+                    if let Err(e) = swap_link(&alias_file, &bin_file) {
+                        error!("failed to update alias {}: {}", alias, e);
+                    }
+                }
+                let result = PackageReceipts::load(app_dir, &name).and_then(|mut receipts| {
+                    receipts.move_aliases(&version);
+                    receipts.save(app_dir)
+                });
+                if let Err(e) = result {
+                    error!("failed to update the receipt of {}: {}", name, e);
+                }
+                updated.push(format!("{} ({} -> {})", name, ip.version, version));
+            }
+            InstallStatus::Unchanged(_) => unchanged.push(name),
+            InstallStatus::Failed => failed.push(name),
+        }
+    }
+
+    println!();
+    if !updated.is_empty() {
+        println!("{} {}", "updated:".green(), updated.join(", "));
+    }
+    if !unchanged.is_empty() {
+        println!("unchanged: {}", unchanged.join(", "));
+    }
+    if !pinned.is_empty() {
+        println!("pinned: {}", pinned.join(", "));
+    }
+    if !failed.is_empty() {
+        println!("{} {}", "failed:".red(), failed.join(", "));
+    }
+    statuses
+}
@@ -1,33 +1,74 @@
-use crate::tool::logger::error;
+use colored::Colorize;
+use std::env::consts::EXE_SUFFIX;
 
-pub fn upgrade() {
-    tokio::task::spawn_blocking(|| {
-        let release_update = match self_update::backends::github::Update::configure()
-            .repo_owner("jinyuli")
-            .repo_name("sys-kaleido")
-            .bin_name("sys-kaleido")
-            .show_download_progress(true)
-            .current_version(self_update::cargo_crate_version!())
-            .build()
-        {
-            Ok(u) => u,
+use super::installer::{get_github_json, verify_bin, GithubRelease};
+use crate::tool::{
+    fs::AppDir,
+    http::{download_bar, download_with_progress},
+    logger::error,
+};
+
+const REPO: &str = "jinyuli/sys-kaleido";
+
+/// replace the running sys-kaleido with the latest release, it's downloaded with network settings.
+pub async fn upgrade(app_dir: &AppDir) {
+    let release: GithubRelease =
+        match get_github_json(&format!("repos/{}/releases/latest", REPO)).await {
+            Ok(r) => r,
             Err(e) => {
                 error!("failed to check update: {}", e);
                 return;
             }
         };
-        match release_update.update() {
-            Ok(s) => match s {
-                self_update::Status::UpToDate(v) => {
-                    println!("sys-kaleido {} is the latest version.", v);
-                }
-                self_update::Status::Updated(v) => {
-                    println!("sys-kaleido has been updated to {}.", v);
-                }
-            },
-            Err(e) => {
-                error!("failed to upgrade: {}", e);
-            }
-        };
-    });
+    let current = self_update::cargo_crate_version!();
+    let latest = release.tag_name.trim_start_matches('v');
+    match self_update::version::bump_is_greater(current, latest) {
+        Ok(true) => {}
+        Ok(false) => {
+            println!("sys-kaleido {} is the latest version.", current);
+            return;
+        }
+        Err(e) => {
+            error!("failed to check update: {}", e);
+            return;
+        }
+    }
+
+    let target = self_update::get_target();
+    let asset = match release.assets.iter().find(|a| a.name.contains(target)) {
+        Some(a) => a,
+        None => {
+            println!(
+                "{}",
+                format!("cannot find sys-kaleido {} for {}", latest, target).red()
+            );
+            return;
+        }
+    };
+    let tmp = match app_dir.create_tmp_dir("sys-kaleido") {
+        Ok(t) => t,
+        Err(e) => {
+            error!("failed to create tmp dir: {}", e);
+            return;
+        }
+    };
+    let new_exe = tmp.path().join(format!("sys-kaleido{}", EXE_SUFFIX));
+    if let Err(e) = download_with_progress(
+        new_exe.clone(),
+        &asset.browser_download_url,
+        &download_bar("sys-kaleido"),
+    )
+    .await
+    {
+        error!("failed to upgrade: {}", e);
+        return;
+    }
+    if let Err(e) = verify_bin(&new_exe) {
+        error!("failed to upgrade: {}", e);
+        return;
+    }
+    match self_update::self_replace::self_replace(&new_exe) {
+        Ok(_) => println!("sys-kaleido has been updated to {}.", latest),
+        Err(e) => error!("failed to upgrade: {}", e),
+    }
 }
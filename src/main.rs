@@ -1,21 +1,36 @@
+use colored::Colorize;
+
 use crate::{
     action::{
         bindle::{
             install as bindle_install, list as bindle_list, uninstall as bindle_uninstall,
             update as bindle_update,
         },
-        config::{check_config, update as config_update},
-        installer::{install, InstallRequest, InstallerContext},
-        list::{list, list_all},
+        cache::{clean as cache_clean, list as cache_list, size as cache_size},
+        config::{check_config, is_config_stale, update as config_update},
+        installer::{
+            install, outdated, versions, InstallRequest, InstallStatus, InstallerContext,
+            OutdatedStatus,
+        },
+        list::{list, list_all, list_versions},
+        migrate_receipts,
+        pin::{pin, skip_pinned, unpin},
+        prune::prune,
         search::search,
+        switch::{rollback, use_version},
         uninstall::uninstall,
+        update::update_all,
         upgrade::upgrade,
     },
     tool::{
         args,
         fs::AppDir,
         global_input::GlobalInput,
-        logger::{debug, init_logger},
+        http,
+        lock::StateLock,
+        logger::{debug, error, init_logger},
+        settings::Settings,
+        state::is_migrated,
     },
 };
 
@@ -28,8 +43,28 @@ async fn main() {
     let mut global_input = GlobalInput::new(&mut stdin);
     let app_dir = AppDir::new().unwrap();
     init_logger(app_dir.get_log_dir()).unwrap();
+    http::init(Settings::load_or_default(app_dir.get_home_dir()).network);
     let command: args::SysKaleidoCommand = argh::from_env();
     debug!("args: {:?}", command);
+    // read-only commands also write state when receipts are migrated or the config file is updated
+    let needs_lock = command.cmd.is_mutating()
+        || !is_migrated(&app_dir)
+        || (command.cmd.checks_config() && is_config_stale(app_dir.get_home_dir()));
+    let _lock = if needs_lock {
+        match StateLock::acquire(&app_dir, command.wait) {
+            Ok(l) => Some(l),
+            Err(e) => {
+                println!("{}", e.to_string().red());
+                std::process::exit(1);
+            }
+        }
+    } else {
+        None
+    };
+    // receipts may be written by the command, so old installs must have theirs first
+    if let Err(e) = migrate_receipts(&app_dir).await {
+        error!("failed to migrate receipts: {}", e);
+    }
     match command.cmd {
         args::TopCommand::Search(cmd) => {
             check_config(app_dir.get_home_dir(), &mut global_input).await;
@@ -44,12 +79,15 @@ async fn main() {
             let context = InstallerContext {
                 rust_abi,
                 force: cmd.force,
+                jobs: cmd.jobs,
+                binstall_sources: Default::default(),
             };
             let requests: Vec<InstallRequest> = if cmd.packages.len() == 1 {
                 vec![InstallRequest {
                     name: cmd.packages[0].clone(),
                     version: cmd.version,
                     alias: cmd.alias,
+                    prerelease: cmd.pre,
                 }]
             } else {
                 cmd.packages
@@ -58,6 +96,7 @@ async fn main() {
                         name: p,
                         version: None,
                         alias: None,
+                        prerelease: cmd.pre,
                     })
                     .collect()
             };
@@ -76,6 +115,8 @@ async fn main() {
                     let context = InstallerContext {
                         rust_abi,
                         force: cmd.force,
+                        jobs: cmd.jobs,
+                        binstall_sources: Default::default(),
                     };
                     bindle_install(cmd.name, &app_dir, &mut global_input, &context).await;
                 }
@@ -90,6 +131,8 @@ async fn main() {
                     let context = InstallerContext {
                         rust_abi,
                         force: cmd.force,
+                        jobs: cmd.jobs,
+                        binstall_sources: Default::default(),
                     };
                     bindle_update(cmd.name, &app_dir, &mut global_input, &context).await;
                 }
@@ -110,28 +153,48 @@ async fn main() {
             let context = InstallerContext {
                 rust_abi,
                 force: cmd.force,
+                jobs: cmd.jobs,
+                binstall_sources: Default::default(),
             };
-            let requests: Vec<InstallRequest> = if cmd.packages.len() == 1 {
-                vec![InstallRequest {
-                    name: cmd.packages[0].clone(),
-                    version: cmd.version,
-                    alias: cmd.alias,
-                }]
+            let statuses = if cmd.all {
+                update_all(&app_dir, &mut global_input, &context, cmd.pre).await
             } else {
-                cmd.packages
-                    .into_iter()
-                    .map(|p| InstallRequest {
-                        name: p,
-                        version: None,
-                        alias: None,
-                    })
-                    .collect()
+                let requests: Vec<InstallRequest> = if cmd.packages.len() == 1 {
+                    vec![InstallRequest {
+                        name: cmd.packages[0].clone(),
+                        version: cmd.version,
+                        alias: cmd.alias,
+                        prerelease: cmd.pre,
+                    }]
+                } else {
+                    cmd.packages
+                        .into_iter()
+                        .map(|p| InstallRequest {
+                            name: p,
+                            version: None,
+                            alias: None,
+                            prerelease: cmd.pre,
+                        })
+                        .collect()
+                };
+
+                let requests = skip_pinned(requests, &app_dir, context.force);
+                install(requests, &app_dir, &mut global_input, &context).await
             };
 
-            install(requests, &app_dir, &mut global_input, &context).await;
+            // nothing is pruned if nothing is updated or an update failed
+            let settings = Settings::load_or_default(app_dir.get_home_dir());
+            if settings.prune.auto
+                && statuses
+                    .iter()
+                    .any(|s| matches!(s, InstallStatus::Installed(_)))
+                && !statuses.contains(&InstallStatus::Failed)
+            {
+                prune(&app_dir, settings.prune.keep, false).await;
+            }
         }
         args::TopCommand::Upgrade(_) => {
-            upgrade();
+            upgrade(&app_dir).await;
             config_update(app_dir.get_home_dir()).await;
         }
         args::TopCommand::Config(cmd) => match cmd.command {
@@ -140,7 +203,9 @@ async fn main() {
             }
         },
         args::TopCommand::List(cmd) => {
-            if cmd.all {
+            if cmd.versions {
+                list_versions(&app_dir).await;
+            } else if cmd.all {
                 list_all(&app_dir).await;
             } else {
                 list(&app_dir).await;
@@ -149,6 +214,57 @@ async fn main() {
         args::TopCommand::Uninstall(cmd) => {
             uninstall(cmd.packages, &app_dir).await;
         }
+        args::TopCommand::Versions(cmd) => {
+            // the question of check_config would break json output
+            if !cmd.json {
+                check_config(app_dir.get_home_dir(), &mut global_input).await;
+            }
+            let rust_abi = match cmd.rust_abi {
+                Some(abi) => abi,
+                None => default_abi(),
+            };
+            versions(cmd.package, &app_dir, &rust_abi, cmd.json).await;
+        }
+        args::TopCommand::Outdated(cmd) => {
+            check_config(app_dir.get_home_dir(), &mut global_input).await;
+            let rust_abi = match cmd.rust_abi {
+                Some(abi) => abi,
+                None => default_abi(),
+            };
+            let status = outdated(&app_dir, &rust_abi).await;
+            if status != OutdatedStatus::UpToDate {
+                std::process::exit(status.exit_code());
+            }
+        }
+        args::TopCommand::Pin(cmd) => {
+            check_config(app_dir.get_home_dir(), &mut global_input).await;
+            pin(cmd.package, &app_dir).await;
+        }
+        args::TopCommand::Unpin(cmd) => {
+            unpin(cmd.package, &app_dir).await;
+        }
+        args::TopCommand::Use(cmd) => {
+            let rust_abi = match cmd.rust_abi {
+                Some(abi) => abi,
+                None => default_abi(),
+            };
+            use_version(cmd.package, &app_dir, &rust_abi).await;
+        }
+        args::TopCommand::Rollback(cmd) => {
+            rollback(cmd.package, &app_dir).await;
+        }
+        args::TopCommand::Prune(cmd) => {
+            let keep = match cmd.keep {
+                Some(k) => k,
+                None => Settings::load_or_default(app_dir.get_home_dir()).prune.keep,
+            };
+            prune(&app_dir, keep, cmd.dry_run).await;
+        }
+        args::TopCommand::Cache(cmd) => match cmd.command {
+            args::CacheSubCommand::List(_) => cache_list(&app_dir).await,
+            args::CacheSubCommand::Clean(_) => cache_clean(&app_dir).await,
+            args::CacheSubCommand::Size(_) => cache_size(&app_dir).await,
+        },
     }
 }
 
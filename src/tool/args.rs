@@ -3,6 +3,10 @@ use argh::FromArgs;
 #[derive(FromArgs, PartialEq, Debug)]
 /// SysKaleido, provides
 pub struct SysKaleidoCommand {
+    #[argh(switch)]
+    /// wait for another running sys-kaleido instead of exiting
+    pub wait: bool,
+
     #[argh(subcommand)]
     pub cmd: TopCommand,
 }
@@ -19,6 +23,43 @@ pub enum TopCommand {
     Bindle(BindleCommand),
     Version(AppVersionCommand),
     Upgrade(AppUpgradeCommand),
+    Versions(VersionsCommand),
+    Outdated(OutdatedCommand),
+    Pin(PinCommand),
+    Unpin(UnpinCommand),
+    Use(UseCommand),
+    Rollback(RollbackCommand),
+    Prune(PruneCommand),
+    Cache(CacheCommand),
+}
+
+impl TopCommand {
+    /// true if the command changes packages, links or state, such commands don't run at the same time.
+    pub fn is_mutating(&self) -> bool {
+        match self {
+            TopCommand::List(_)
+            | TopCommand::Search(_)
+            | TopCommand::Version(_)
+            | TopCommand::Versions(_)
+            | TopCommand::Outdated(_) => false,
+            TopCommand::Bindle(cmd) => !matches!(cmd.command, BindleSubCommand::List(_)),
+            TopCommand::Cache(cmd) => matches!(cmd.command, CacheSubCommand::Clean(_)),
+            _ => true,
+        }
+    }
+
+    /// true if the command checks the config file, which may be downloaded again.
+    pub fn checks_config(&self) -> bool {
+        match self {
+            TopCommand::Search(_)
+            | TopCommand::Bindle(_)
+            | TopCommand::Update(_)
+            | TopCommand::Outdated(_)
+            | TopCommand::Pin(_) => true,
+            TopCommand::Versions(cmd) => !cmd.json,
+            _ => false,
+        }
+    }
 }
 
 #[derive(FromArgs, PartialEq, Debug)]
@@ -51,6 +92,37 @@ pub enum ConfigSubCommand {
 #[argh(subcommand, name = "update")]
 pub struct ConfigUpdateCommand {}
 
+#[derive(FromArgs, PartialEq, Debug)]
+/// downloaded files which are reused by installs.
+#[argh(subcommand, name = "cache")]
+pub struct CacheCommand {
+    #[argh(subcommand)]
+    pub command: CacheSubCommand,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand)]
+pub enum CacheSubCommand {
+    List(CacheListCommand),
+    Clean(CacheCleanCommand),
+    Size(CacheSizeCommand),
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// list cached files.
+#[argh(subcommand, name = "list")]
+pub struct CacheListCommand {}
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// remove all cached files.
+#[argh(subcommand, name = "clean")]
+pub struct CacheCleanCommand {}
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// show the size of cached files.
+#[argh(subcommand, name = "size")]
+pub struct CacheSizeCommand {}
+
 #[derive(FromArgs, PartialEq, Debug)]
 /// bundles commands.
 #[argh(subcommand, name = "bindle")]
@@ -75,6 +147,91 @@ pub struct ListCommand {
     #[argh(switch)]
     /// list all packages, no matter it's installed or not
     pub all: bool,
+
+    #[argh(switch)]
+    /// list all installed versions of installed packages, the active one is marked with '*'
+    pub versions: bool,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// list remote versions of a package.
+#[argh(subcommand, name = "versions")]
+pub struct VersionsCommand {
+    #[argh(option, arg_name="rust-abi")]
+    /// for rust application, choose which abi to use
+    /// by default, it's 'msvc' on Windows, and 'gnu' for other OS.
+    pub rust_abi: Option<String>,
+
+    #[argh(switch)]
+    /// print versions in json
+    pub json: bool,
+
+    #[argh(positional)]
+    pub package: String,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// pin a package, so it's skipped by update, e.g. 'eza' or 'eza@0.18.0'.
+#[argh(subcommand, name = "pin")]
+pub struct PinCommand {
+    /// package name, with an optional version, the installed version is used by default
+    #[argh(positional)]
+    pub package: String,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// unpin a package.
+#[argh(subcommand, name = "unpin")]
+pub struct UnpinCommand {
+    /// package name
+    #[argh(positional)]
+    pub package: String,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// use an installed version of a package, e.g. 'eza@0.18.0', nothing is downloaded.
+#[argh(subcommand, name = "use")]
+pub struct UseCommand {
+    /// package name and version
+    #[argh(positional)]
+    pub package: String,
+
+    #[argh(option, arg_name="rust-abi")]
+    /// for rust application, choose which abi to use
+    /// by default, it's 'msvc' on Windows, and 'gnu' for other OS.
+    pub rust_abi: Option<String>,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// activate the previously active version of a package.
+#[argh(subcommand, name = "rollback")]
+pub struct RollbackCommand {
+    /// package name
+    #[argh(positional)]
+    pub package: String,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// remove old versions which are not active, not pinned and beyond the newest ones.
+#[argh(subcommand, name = "prune")]
+pub struct PruneCommand {
+    /// how many newest versions of a package are kept, by default it's 'keep' in settings.toml, or 2.
+    #[argh(option)]
+    pub keep: Option<usize>,
+
+    #[argh(switch)]
+    /// only print versions which would be removed
+    pub dry_run: bool,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// list installed packages which have newer releases, exit with 1 if any.
+#[argh(subcommand, name = "outdated")]
+pub struct OutdatedCommand {
+    #[argh(option, arg_name="rust-abi")]
+    /// for rust application, choose which abi to use
+    /// by default, it's 'msvc' on Windows, and 'gnu' for other OS.
+    pub rust_abi: Option<String>,
 }
 
 #[derive(FromArgs, PartialEq, Debug)]
@@ -99,6 +256,10 @@ pub struct BindleInstallCommand {
     /// otherwise, the package will be skipped if the given version has been installed.
     pub force: bool,
 
+    #[argh(option, default = "4")]
+    /// how many packages are downloaded at the same time, it's 4 by default.
+    pub jobs: usize,
+
     #[argh(positional)]
     pub name: String,
 }
@@ -126,6 +287,10 @@ pub struct BindleUpdateCommand {
     /// otherwise, the package will be skipped if the given version has been installed.
     pub force: bool,
 
+    #[argh(option, default = "4")]
+    /// how many packages are downloaded at the same time, it's 4 by default.
+    pub jobs: usize,
+
     #[argh(positional)]
     pub name: String,
 }
@@ -143,6 +308,7 @@ pub struct BindleUninstallCommand {
 #[argh(subcommand, name = "install")]
 pub struct InstallCommand {
     /// version to install, only valid for single package
+    /// it could be a semver requirement, e.g. '^0.18' or '>=4.8,<5'
     #[argh(option, short = 'v')]
     pub version: Option<String>,
 
@@ -160,6 +326,14 @@ pub struct InstallCommand {
     /// otherwise, the package will be skipped if the given version has been installed.
     pub force: bool,
 
+    #[argh(option, default = "4")]
+    /// how many packages are downloaded at the same time, it's 4 by default.
+    pub jobs: usize,
+
+    #[argh(switch)]
+    /// include prereleases, e.g. release candidates, when finding the version.
+    pub pre: bool,
+
     /// package name list, separated by whitespace
     #[argh(positional)]
     pub packages: Vec<String>,
@@ -179,6 +353,7 @@ pub struct UninstallCommand {
 #[argh(subcommand, name = "update")]
 pub struct UpdateCommand {
     /// version to install, only valid for single package
+    /// it could be a semver requirement, e.g. '^0.18' or '>=4.8,<5'
     #[argh(option, short = 'v')]
     pub version: Option<String>,
 
@@ -196,6 +371,18 @@ pub struct UpdateCommand {
     /// otherwise, the package will be skipped if the given version has been installed.
     pub force: bool,
 
+    #[argh(option, default = "4")]
+    /// how many packages are downloaded at the same time, it's 4 by default.
+    pub jobs: usize,
+
+    #[argh(switch)]
+    /// update all installed packages, pinned packages are skipped unless --force.
+    pub all: bool,
+
+    #[argh(switch)]
+    /// include prereleases, e.g. release candidates, when finding the version.
+    pub pre: bool,
+
     /// package name list, separated by whitespace
     #[argh(positional)]
     pub packages: Vec<String>,
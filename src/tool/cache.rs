@@ -0,0 +1,346 @@
+use indicatif::ProgressBar;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+use super::fs::{sha256_file, AppDir, FSError};
+use super::http::{self, download_with_progress};
+use super::logger::{debug, error};
+use super::settings::Settings;
+use super::state::unix_now;
+
+pub const CACHE_INDEX_FILE: &str = "index.toml";
+pub const CACHE_FILES_DIR: &str = "files";
+
+pub type Result<T> = std::result::Result<T, CacheError>;
+
+/// downloaded files by their keys, saved in `cache/index.toml`, files are saved in `cache/files/<sha256>`.
+/// a key is the url and the identity of the asset, see `cache_key`.
+#[derive(Deserialize, Serialize, Debug, Default, PartialEq)]
+pub struct CacheIndex {
+    #[serde(default)]
+    pub entries: BTreeMap<String, CacheEntry>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct CacheEntry {
+    pub sha256: String,
+    pub file_name: String,
+    pub size: u64,
+    pub last_used: u64,
+}
+
+impl CacheIndex {
+    /// number of files, a file downloaded from several urls is counted once.
+    pub fn file_count(&self) -> usize {
+        self.entries
+            .values()
+            .map(|e| e.sha256.as_str())
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
+    /// size of all files, a file downloaded from several urls is counted once.
+    pub fn total_size(&self) -> u64 {
+        let mut seen = HashSet::new();
+        self.entries
+            .values()
+            .filter(|e| seen.insert(e.sha256.as_str()))
+            .map(|e| e.size)
+            .sum()
+    }
+}
+
+/// a content addressed cache of downloaded files.
+pub struct DownloadCache {
+    dir: PathBuf,
+    /// in bytes
+    max_size: u64,
+}
+
+impl DownloadCache {
+    pub fn new(dir: &Path, max_size: u64) -> Self {
+        DownloadCache {
+            dir: dir.to_path_buf(),
+            max_size,
+        }
+    }
+
+    /// the cache in sys-kaleido home, its max size is read from settings.
+    pub fn open(app_dir: &AppDir) -> Self {
+        let settings = Settings::load_or_default(app_dir.get_home_dir());
+        Self::new(
+            app_dir.get_cache_dir(),
+            settings.cache.max_size * 1024 * 1024,
+        )
+    }
+
+    pub fn max_size(&self) -> u64 {
+        self.max_size
+    }
+
+    pub fn load_index(&self) -> Result<CacheIndex> {
+        let file = self.dir.join(CACHE_INDEX_FILE);
+        if !file.exists() {
+            return Ok(CacheIndex::default());
+        }
+        let content = fs::read_to_string(file)?;
+        Ok(toml::from_str(&content)?)
+    }
+
+    fn save_index(&self, index: &CacheIndex) -> Result<()> {
+        let content = toml::to_string(index)?;
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.dir.join(CACHE_INDEX_FILE), content)?;
+        Ok(())
+    }
+
+    fn file_path(&self, sha256: &str) -> PathBuf {
+        self.dir.join(CACHE_FILES_DIR).join(sha256)
+    }
+
+    /// the cached file of the key, it's removed if its size doesn't match,
+    /// the sha256 isn't checked again since files are named by it.
+    pub fn lookup(&self, key: &str) -> Result<Option<PathBuf>> {
+        if self.max_size == 0 {
+            return Ok(None);
+        }
+        let mut index = self.load_index()?;
+        let entry = match index.entries.get_mut(key) {
+            Some(e) => e,
+            None => return Ok(None),
+        };
+        let path = self.file_path(&entry.sha256);
+        if fs::metadata(&path).is_ok_and(|m| m.is_file() && m.len() == entry.size) {
+            entry.last_used = unix_now();
+            self.save_index(&index)?;
+            return Ok(Some(path));
+        }
+
+        debug!("cached file of {} is missing or broken", key);
+        let sha256 = entry.sha256.clone();
+        index.entries.retain(|_, e| e.sha256 != sha256);
+        if path.exists() {
+            fs::remove_file(&path)?;
+        }
+        self.save_index(&index)?;
+        Ok(None)
+    }
+
+    /// the most recently used file downloaded from the url, whatever its identity is.
+    pub fn lookup_url(&self, url: &str) -> Result<Option<PathBuf>> {
+        if self.max_size == 0 {
+            return Ok(None);
+        }
+        let prefix = format!("{}#", url);
+        let key = self
+            .load_index()?
+            .entries
+            .into_iter()
+            .filter(|(k, _)| k == url || k.starts_with(&prefix))
+            .max_by_key(|(_, e)| e.last_used)
+            .map(|(k, _)| k);
+        match key {
+            Some(k) => self.lookup(&k),
+            None => Ok(None),
+        }
+    }
+
+    /// copy the downloaded file into the cache, then old files are removed if the cache is too large.
+    pub fn store(&self, key: &str, file: &Path) -> Result<()> {
+        if self.max_size == 0 {
+            return Ok(());
+        }
+        let sha256 = sha256_file(file)?;
+        let path = self.file_path(&sha256);
+        if !path.is_file() {
+            fs::create_dir_all(self.dir.join(CACHE_FILES_DIR))?;
+            // a partly copied file is never seen by its name
+            let tmp_path = self.file_path(&format!(".{}.tmp", sha256));
+            fs::copy(file, &tmp_path)?;
+            fs::rename(&tmp_path, &path)?;
+        }
+
+        let mut index = self.load_index()?;
+        index.entries.insert(
+            key.to_string(),
+            CacheEntry {
+                sha256,
+                file_name: file
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                size: fs::metadata(&path)?.len(),
+                last_used: unix_now(),
+            },
+        );
+        self.evict(&mut index, key)?;
+        self.save_index(&index)
+    }
+
+    /// remove least recently used files until the cache fits in the max size, the `newest` key is the last one to remove.
+    fn evict(&self, index: &mut CacheIndex, newest: &str) -> Result<()> {
+        let mut entries: Vec<(String, CacheEntry)> = index
+            .entries
+            .iter()
+            .map(|(url, e)| (url.clone(), e.clone()))
+            .collect();
+        entries.sort_by_key(|(url, e)| (url == newest, e.last_used));
+        for (url, entry) in entries {
+            if index.total_size() <= self.max_size {
+                break;
+            }
+            debug!("evict {} from cache", url);
+            index.entries.remove(&url);
+            if !index.entries.values().any(|e| e.sha256 == entry.sha256) {
+                let path = self.file_path(&entry.sha256);
+                if path.exists() {
+                    fs::remove_file(path)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// remove all cached files, the freed size is returned.
+    pub fn clean(&self) -> Result<u64> {
+        let size = self.load_index()?.total_size();
+        let files_dir = self.dir.join(CACHE_FILES_DIR);
+        if files_dir.exists() {
+            fs::remove_dir_all(files_dir)?;
+        }
+        self.save_index(&CacheIndex::default())?;
+        Ok(size)
+    }
+}
+
+/// the key of a downloaded file, the same url may serve another file later, e.g. a nightly release,
+/// so the identity of the asset, e.g. its version and publish time, is a part of the key.
+pub fn cache_key(url: &str, identity: Option<&str>) -> String {
+    match identity {
+        Some(i) => format!("{}#{}", url, i),
+        None => url.to_string(),
+    }
+}
+
+/// copy the file from cache if it has been downloaded, otherwise download it and cache it.
+/// any cached file of the url is used if the identity is unknown.
+/// the cache never fails a download, its errors are only logged.
+pub async fn download_cached(
+    app_dir: &AppDir,
+    to_file: &Path,
+    url: &str,
+    identity: Option<&str>,
+    pb: &ProgressBar,
+) -> http::Result<()> {
+    let cache = DownloadCache::open(app_dir);
+    let key = cache_key(url, identity);
+    let cached = match identity {
+        Some(_) => cache.lookup(&key),
+        None => cache.lookup_url(url),
+    };
+    match cached {
+        Ok(Some(path)) => {
+            fs::copy(path, to_file)?;
+            pb.suspend(|| println!("use cached file of {}", url));
+            pb.finish_and_clear();
+            return Ok(());
+        }
+        Ok(None) => {}
+        Err(e) => error!("failed to read cache of {}: {}", url, e),
+    }
+    download_with_progress(to_file.to_path_buf(), url, pb).await?;
+    if let Err(e) = cache.store(&key, to_file) {
+        error!("failed to cache {}: {}", url, e);
+    }
+    Ok(())
+}
+
+#[derive(Error, Debug)]
+pub enum CacheError {
+    #[error("file system error: {0}")]
+    Fs(#[from] std::io::Error),
+    #[error("file system error: {0}")]
+    ToolFs(#[from] FSError),
+    #[error("cache index error: {0}")]
+    Read(#[from] toml::de::Error),
+    #[error("cache index error: {0}")]
+    Write(#[from] toml::ser::Error),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_store_and_lookup() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = DownloadCache::new(&dir.path().join("cache"), 1024);
+        let file = dir.path().join("tool.tar.gz");
+        fs::write(&file, "tool").unwrap();
+
+        assert_eq!(None, cache.lookup("https://example.com/tool").unwrap());
+        cache.store("https://example.com/tool", &file).unwrap();
+        // the same content from another url is stored once
+        cache
+            .store("https://mirror.example.com/tool", &file)
+            .unwrap();
+        let path = cache.lookup("https://example.com/tool").unwrap().unwrap();
+        assert_eq!("tool", fs::read_to_string(&path).unwrap());
+        let index = cache.load_index().unwrap();
+        assert_eq!(2, index.entries.len());
+        assert_eq!(1, index.file_count());
+        assert_eq!(4, index.total_size());
+
+        // a broken file is not used
+        fs::write(&path, "broken").unwrap();
+        assert_eq!(None, cache.lookup("https://example.com/tool").unwrap());
+        assert!(cache.load_index().unwrap().entries.is_empty());
+
+        cache.store("https://example.com/tool", &file).unwrap();
+        assert_eq!(4, cache.clean().unwrap());
+        assert!(cache.load_index().unwrap().entries.is_empty());
+    }
+
+    #[test]
+    fn test_identity() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = DownloadCache::new(&dir.path().join("cache"), 1024);
+        let file = dir.path().join("tool.tar.gz");
+        fs::write(&file, "nightly").unwrap();
+        let url = "https://example.com/nightly/tool.tar.gz";
+        let key = cache_key(url, Some("nightly@2024-01-01"));
+        cache.store(&key, &file).unwrap();
+
+        // a nightly published again is not the cached one
+        let republished = cache_key(url, Some("nightly@2024-01-02"));
+        assert_eq!(None, cache.lookup(&republished).unwrap());
+        assert!(cache.lookup(&key).unwrap().is_some());
+        assert!(cache.lookup_url(url).unwrap().is_some());
+        assert_eq!(
+            None,
+            cache.lookup_url("https://example.com/nightly").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_evict() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = DownloadCache::new(&dir.path().join("cache"), 10);
+        for (name, content) in [("b", "bbbbbb"), ("a", "aaaaaa")] {
+            let file = dir.path().join(name);
+            fs::write(&file, content).unwrap();
+            cache.store(name, &file).unwrap();
+        }
+        let index = cache.load_index().unwrap();
+        assert_eq!(vec!["a"], index.entries.keys().collect::<Vec<_>>());
+        assert_eq!(
+            1,
+            fs::read_dir(dir.path().join("cache").join(CACHE_FILES_DIR))
+                .unwrap()
+                .count()
+        );
+    }
+}
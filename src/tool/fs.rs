@@ -2,11 +2,13 @@ use super::logger::debug;
 use directories::UserDirs;
 use flate2::read::GzDecoder;
 use sevenz_rust::default_entry_extract_fn;
+use sha2::{Digest, Sha256};
 use std::fs;
 use std::io;
 use std::path::Component;
 use std::path::{Path, PathBuf};
 use tar::Archive;
+use tempfile::TempDir;
 use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, FSError>;
@@ -18,47 +20,72 @@ pub struct AppDir {
     bin_dir: PathBuf,
     packages_dir: PathBuf,
     alias_dir: PathBuf,
+    state_dir: PathBuf,
+    tmp_dir: PathBuf,
+    cache_dir: PathBuf,
 }
 
 impl AppDir {
     pub fn new() -> Result<Self> {
         if let Some(user_dir) = UserDirs::new() {
             let user_home = user_dir.home_dir();
-            let home = user_home.join(".sys-kaleido");
-            if !home.exists() || !home.is_dir() {
-                fs::create_dir(&home)?;
-            }
+            Self::from_home(user_home.join(".sys-kaleido"))
+        } else {
+            Err(FSError::AppDirError())
+        }
+    }
 
-            let packages_dir = home.join("packages");
-            if !packages_dir.exists() || !packages_dir.is_dir() {
-                fs::create_dir(&packages_dir)?;
-            }
+    /// use the given directory as sys-kaleido home, all sub directories are created if they don't exist.
+    pub fn from_home(home: PathBuf) -> Result<Self> {
+        if !home.exists() || !home.is_dir() {
+            fs::create_dir(&home)?;
+        }
 
-            let alias_dir = home.join("alias");
-            if !alias_dir.exists() || !alias_dir.is_dir() {
-                fs::create_dir(&alias_dir)?;
-            }
+        let packages_dir = home.join("packages");
+        if !packages_dir.exists() || !packages_dir.is_dir() {
+            fs::create_dir(&packages_dir)?;
+        }
 
-            let bin_dir = home.join("bin");
-            if !bin_dir.exists() || !bin_dir.is_dir() {
-                fs::create_dir(&bin_dir)?;
-            }
+        let alias_dir = home.join("alias");
+        if !alias_dir.exists() || !alias_dir.is_dir() {
+            fs::create_dir(&alias_dir)?;
+        }
 
-            let log_dir = home.join("log");
-            if !log_dir.exists() || !log_dir.is_dir() {
-                fs::create_dir(&log_dir)?;
-            }
+        let bin_dir = home.join("bin");
+        if !bin_dir.exists() || !bin_dir.is_dir() {
+            fs::create_dir(&bin_dir)?;
+        }
 
-            Ok(AppDir {
-                home_dir: home,
-                log_dir,
-                packages_dir,
-                alias_dir,
-                bin_dir,
-            })
-        } else {
-            Err(FSError::AppDirError())
+        let log_dir = home.join("log");
+        if !log_dir.exists() || !log_dir.is_dir() {
+            fs::create_dir(&log_dir)?;
         }
+
+        let state_dir = home.join("state");
+        if !state_dir.exists() || !state_dir.is_dir() {
+            fs::create_dir(&state_dir)?;
+        }
+
+        let tmp_dir = home.join("tmp");
+        if !tmp_dir.exists() || !tmp_dir.is_dir() {
+            fs::create_dir(&tmp_dir)?;
+        }
+
+        let cache_dir = home.join("cache");
+        if !cache_dir.exists() || !cache_dir.is_dir() {
+            fs::create_dir(&cache_dir)?;
+        }
+
+        Ok(AppDir {
+            home_dir: home,
+            log_dir,
+            packages_dir,
+            alias_dir,
+            bin_dir,
+            state_dir,
+            tmp_dir,
+            cache_dir,
+        })
     }
 
     pub fn get_home_dir(&self) -> &Path {
@@ -80,6 +107,23 @@ impl AppDir {
     pub fn get_bin_dir(&self) -> &Path {
         self.bin_dir.as_path()
     }
+
+    /// user state, e.g. pinned packages.
+    pub fn get_state_dir(&self) -> &Path {
+        self.state_dir.as_path()
+    }
+
+    /// downloaded files, which are reused by later installs.
+    pub fn get_cache_dir(&self) -> &Path {
+        self.cache_dir.as_path()
+    }
+
+    /// create a unique directory in `tmp` for one install, it's removed on drop.
+    pub fn create_tmp_dir(&self, prefix: &str) -> Result<TempDir> {
+        Ok(tempfile::Builder::new()
+            .prefix(&format!("{}-", prefix))
+            .tempdir_in(&self.tmp_dir)?)
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -97,6 +141,7 @@ pub fn get_file_type<T: AsRef<Path>>(file_name: T) -> FileType {
                 "zip" => FileType::Compression,
                 "7z" => FileType::Compression,
                 "gz" => FileType::Compression,
+                "tgz" => FileType::Compression,
                 _ => FileType::Unknown,
             },
             None => FileType::Unknown,
@@ -137,6 +182,57 @@ pub fn decompress(file_path: &Path, to_path: &Path) -> Result<Option<String>> {
     Ok(result)
 }
 
+/// total size of files in the directory, links are not followed.
+pub fn dir_size(path: &Path) -> Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.path().symlink_metadata()?;
+        if metadata.is_dir() {
+            size += dir_size(&entry.path())?;
+        } else {
+            size += metadata.len();
+        }
+    }
+    Ok(size)
+}
+
+/// sha256 of a file in lowercase hex.
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// read the first file in an archive which the predicate accepts, the archive type is decided by `archive_name`.
+pub fn read_from_archive<F>(archive_name: &str, content: &[u8], predicate: F) -> Result<Option<String>>
+where
+    F: Fn(&Path) -> bool,
+{
+    let mut result = String::new();
+    if archive_name.ends_with(".zip") {
+        let mut archive = zip::ZipArchive::new(io::Cursor::new(content))?;
+        for i in 0..archive.len() {
+            let mut item = archive.by_index(i)?;
+            if item.enclosed_name().is_some_and(&predicate) {
+                io::Read::read_to_string(&mut item, &mut result)?;
+                return Ok(Some(result));
+            }
+        }
+    } else {
+        let mut archive = Archive::new(GzDecoder::new(content));
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            if predicate(&entry.path()?) {
+                io::Read::read_to_string(&mut entry, &mut result)?;
+                return Ok(Some(result));
+            }
+        }
+    }
+    Ok(None)
+}
+
 fn deflate(file_path: &Path, to_path: &Path) -> Result<Option<String>> {
     let tar_gz = fs::File::open(file_path)?;
     let tar = GzDecoder::new(tar_gz);
@@ -408,6 +504,41 @@ pub fn remove_link(dir: &Path) -> Result<()> {
     Ok(())
 }
 
+/// point the link to `origin` by renaming a temporary link over it, so the link always exists.
+pub fn swap_link(link: &Path, origin: &Path) -> Result<()> {
+    let mut tmp_name = std::ffi::OsString::from(".");
+    tmp_name.push(link.file_name().unwrap_or_default());
+    tmp_name.push(".tmp");
+    let tmp_link = link.with_file_name(tmp_name);
+    if tmp_link.symlink_metadata().is_ok() {
+        remove_link(&tmp_link)?;
+    }
+    make_link(&tmp_link, origin)?;
+    if let Err(e) = fs::rename(&tmp_link, link) {
+        let _ = remove_link(&tmp_link);
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+/// create `to` as another link to the file which `from` links to.
+#[cfg(not(target_os = "windows"))]
+pub fn copy_link(from: &Path, to: &Path) -> Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(from)?, to)?;
+    Ok(())
+}
+
+/// create `to` as another link to the file which `from` links to.
+#[cfg(target_os = "windows")]
+pub fn copy_link(from: &Path, to: &Path) -> Result<()> {
+    if from.is_symlink() {
+        make_link(to, &fs::read_link(from)?)
+    } else {
+        fs::hard_link(from, to)?;
+        Ok(())
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum FSError {
     #[error("{0}")]
@@ -419,6 +550,7 @@ pub enum FSError {
     #[error("{0}")]
     SevenZ(#[from] sevenz_rust::Error),
     #[error("make symbol link error")]
+    #[cfg_attr(not(target_os = "windows"), allow(dead_code))]
     LinkError(),
     #[error("file type '{0}' is not supported")]
     UnsupportedFile(&'static str),
@@ -439,9 +571,68 @@ mod test {
         assert_eq!(FileType::Compression, get_file_type("path/test.zip"));
         assert_eq!(FileType::Compression, get_file_type("path/test.7z"));
         assert_eq!(FileType::Compression, get_file_type("path/test.tar.gz"));
+        assert_eq!(FileType::Compression, get_file_type("path/test.tgz"));
         assert_eq!(FileType::Unknown, get_file_type("path/test.x"));
     }
 
+    #[test]
+    fn test_create_tmp_dir() -> std::result::Result<(), FSError> {
+        let dir = tempdir()?;
+        let app_dir = AppDir::from_home(dir.path().join("home"))?;
+        let first = app_dir.create_tmp_dir("tool")?;
+        let second = app_dir.create_tmp_dir("tool")?;
+        assert_ne!(first.path(), second.path());
+        assert!(first.path().starts_with(app_dir.get_home_dir().join("tmp")));
+        let path = first.path().to_path_buf();
+        drop(first);
+        assert!(!path.exists());
+        assert!(second.path().exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_dir_size() -> std::result::Result<(), FSError> {
+        let dir = tempdir()?;
+        fs::write(dir.path().join("a"), "abc")?;
+        create_dir(dir.path().join("sub"))?;
+        fs::write(dir.path().join("sub").join("b"), "de")?;
+        assert_eq!(5, dir_size(dir.path())?);
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_swap_link() -> std::result::Result<(), FSError> {
+        let dir = tempdir()?;
+        let old = dir.path().join("old");
+        let new = dir.path().join("new");
+        fs::write(&old, "old")?;
+        fs::write(&new, "new")?;
+        let link = dir.path().join("link");
+        make_link(&link, &old)?;
+
+        copy_link(&link, &dir.path().join("backup"))?;
+        swap_link(&link, &new)?;
+        assert_eq!(new, fs::read_link(&link)?);
+        assert_eq!(old, fs::read_link(dir.path().join("backup"))?);
+        assert!(!dir.path().join(".link.tmp").exists());
+        swap_link(&dir.path().join("other"), &new)?;
+        assert_eq!(new, fs::read_link(dir.path().join("other"))?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sha256_file() -> std::result::Result<(), FSError> {
+        let dir = tempdir()?;
+        let file_path = dir.path().join("file");
+        fs::write(&file_path, "abc")?;
+        assert_eq!(
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            sha256_file(&file_path)?
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_make_link_file() -> std::result::Result<(), FSError> {
         let dir = tempdir()?;
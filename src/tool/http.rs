@@ -1,89 +1,61 @@
 use futures_util::StreamExt;
 use indicatif::{ProgressBar, ProgressState, ProgressStyle};
-use log::{debug, error, info};
+use log::{debug, error, info, trace};
 use reqwest::{
     self,
-    header::{HeaderMap, USER_AGENT},
+    header::{HeaderMap, CONTENT_RANGE, ETAG, IF_RANGE, LAST_MODIFIED, RANGE},
+    Certificate, NoProxy, Proxy, StatusCode,
 };
-use std::fs::File;
+use serde::de::DeserializeOwned;
+use std::fs::{self, File, OpenOptions};
 use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::sync::mpsc::{channel, Sender};
+use std::sync::OnceLock;
+use std::time::Duration;
 use thiserror::Error;
-use tokio::task;
+use tokio::time::{sleep, timeout};
+
+use super::settings::NetworkSettings;
 
 pub type Result<T> = std::result::Result<T, HttpError>;
 
-pub async fn download_with_progress(to_file: PathBuf, download_url: &str) -> Result<()> {
-    let url = download_url.to_string();
-    let (sender, receiver) = channel();
-    task::spawn(async move {
-        let s = sender.clone();
-        let mut headers = HeaderMap::new();
-        headers.insert(USER_AGENT, "sys-kaleido".parse().unwrap());
-        match download(&url, &to_file, headers, s).await {
-            Err(e) => {
-                let _ = sender.send(ProgressInfo::Error(e.to_string()));
-            }
-            Ok(_) => {
-                let _ = sender.send(ProgressInfo::Complete());
-            }
+/// the delay between retries is never longer than this, in milliseconds.
+const MAX_RETRY_DELAY: u64 = 30_000;
+
+static NETWORK: OnceLock<NetworkSettings> = OnceLock::new();
+
+/// network settings of all requests, default settings are used if it's not called before the first request.
+pub fn init(settings: NetworkSettings) {
+    if NETWORK.set(settings).is_err() {
+        debug!("network settings have been set");
+    }
+}
+
+fn network() -> &'static NetworkSettings {
+    NETWORK.get_or_init(NetworkSettings::default)
+}
+
+/// a progress bar for downloading, its length is set when the download starts.
+pub fn download_bar(name: &str) -> ProgressBar {
+    let pb = ProgressBar::new(0);
+    pb.set_prefix(name.to_string());
+    pb
+}
+
+/// download the file and show progress in the bar.
+pub async fn download_with_progress(to_file: PathBuf, download_url: &str, pb: &ProgressBar) -> Result<()> {
+    match download(download_url, &to_file, HeaderMap::new(), pb, network()).await {
+        Ok(_) => {
+            info!("download file successfully");
+            pb.finish_with_message("downloaded");
+            Ok(())
         }
-    });
-
-    let mut pb: Option<ProgressBar> = None;
-    while let Ok(p) = receiver.recv() {
-        match p {
-            ProgressInfo::Complete() => {
-                info!("download file successfully");
-                if let Some(p) = pb.as_ref() {
-                    p.finish_with_message("download file successfully");
-                }
-                break;
-            }
-            ProgressInfo::Error(e) => {
-                error!("failed to download file: {}", e);
-                if let Some(p) = pb.as_ref() {
-                    p.finish_with_message(format!("failed to download file: {}", e));
-                }
-                return Err(HttpError::General(e));
-            }
-            ProgressInfo::Progress { current, total: _ } => {
-                // info!("process: {}/{:?}", current, total);
-                if let Some(p) = pb.as_ref() {
-                    p.set_position(current);
-                }
-            }
-            ProgressInfo::Start(size) => {
-                if size > 0 {
-                    let _pb = ProgressBar::new(size);
-                    _pb.set_style(ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({eta})")
-                        .unwrap()
-                        .with_key("eta", |state: &ProgressState, w: &mut dyn std::fmt::Write| write!(w, "{:.1}s", state.eta().as_secs_f64()).unwrap())
-                        .progress_chars("#>-"));
-                    pb = Some(_pb);
-                } else {
-                    let _pb = ProgressBar::new_spinner();
-                    _pb.set_style(
-                        ProgressStyle::with_template("{spinner:.blue} {msg}")
-                            .unwrap()
-                            .tick_strings(&[
-                                "▹▹▹▹▹",
-                                "▸▹▹▹▹",
-                                "▹▸▹▹▹",
-                                "▹▹▸▹▹",
-                                "▹▹▹▸▹",
-                                "▹▹▹▹▸",
-                                "▪▪▪▪▪",
-                            ]),
-                    );
-                    _pb.set_message("Downloading...");
-                    pb = Some(_pb);
-                }
-            }
+        Err(e) => {
+            error!("failed to download file: {}", e);
+            pb.abandon_with_message(format!("failed to download file: {}", e));
+            Err(e)
         }
     }
-    Ok(())
 }
 
 pub async fn download_file(url: &str, to_file: &Path) -> Result<()> {
@@ -94,50 +66,218 @@ pub async fn download_file(url: &str, to_file: &Path) -> Result<()> {
     Ok(())
 }
 
+pub async fn get_json<T: DeserializeOwned>(url: &str, headers: HeaderMap) -> Result<T> {
+    let client = get_client().await?;
+    debug!("get json from {}", url);
+    let res = client
+        .get(url)
+        .headers(headers)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(res.json::<T>().await?)
+}
+
+pub async fn get_text(url: &str) -> Result<String> {
+    let client = get_client().await?;
+    debug!("get text from {}", url);
+    let res = client
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(res.text().await?)
+}
+
+/// true if the url could be downloaded, only the headers are requested.
+pub async fn exists(url: &str) -> Result<bool> {
+    let client = get_client().await?;
+    debug!("check {}", url);
+    let res = client.head(url).send().await?;
+    Ok(res.status().is_success())
+}
+
+/// download to a `.part` file first, failed downloads are retried and resumed from the part file
+/// if the server supports ranges and the remote file is not changed.
 async fn download(
     url: &str,
     to_file: &Path,
-    headers: reqwest::header::HeaderMap,
-    sender: Sender<ProgressInfo>,
+    headers: HeaderMap,
+    pb: &ProgressBar,
+    settings: &NetworkSettings,
 ) -> Result<()> {
-    let client = get_client().await?;
-    let res = client.get(url).headers(headers).send().await?;
-    debug!("{:?}", res.headers());
-    let content_len = res.content_length();
-    let mut stream = res.bytes_stream();
-    let mut file = File::create(to_file)?;
-    let mut downloaded_bytes: u64 = 0;
-    let total = content_len.unwrap_or(0);
-    let _ = sender.send(ProgressInfo::Start(total));
-    while let Some(chunk) = stream.next().await {
-        match chunk {
-            Ok(c) => {
-                file.write_all(&c)?;
-                downloaded_bytes += c.len() as u64;
-                let _ = sender.send(ProgressInfo::Progress {
-                    current: downloaded_bytes,
-                    total: content_len,
-                });
-            }
-            Err(e) => {
-                return Err(HttpError::General(e.to_string()));
+    let client = build_client(settings)?;
+    let part_file = part_path(to_file);
+    let mut retries = 0;
+    loop {
+        match download_part(&client, url, &part_file, headers.clone(), pb, settings).await {
+            Ok(_) => break,
+            Err(e) if retries < settings.retries && e.is_retryable() => {
+                retries += 1;
+                let delay = retry_delay(settings.retry_delay, retries);
+                debug!("failed to download {}: {}, retry in {:?}", url, e, delay);
+                pb.set_message(format!("retry {}/{}", retries, settings.retries));
+                sleep(delay).await;
             }
+            Err(e) => return Err(e),
+        }
+    }
+    fs::rename(&part_file, to_file)?;
+    remove_if_exists(&validator_path(&part_file))?;
+    Ok(())
+}
+
+/// download the rest of the part file, or the whole file if the server doesn't send a range.
+async fn download_part(
+    client: &reqwest::Client,
+    url: &str,
+    part_file: &Path,
+    headers: HeaderMap,
+    pb: &ProgressBar,
+    settings: &NetworkSettings,
+) -> Result<()> {
+    let read_timeout = Duration::from_secs(settings.read_timeout);
+    let validator_file = validator_path(part_file);
+    // a part file is only resumed if it's known which version of the remote file it is
+    let validator = fs::read_to_string(&validator_file).ok();
+    let offset = match validator {
+        Some(_) => fs::metadata(part_file).map(|m| m.len()).unwrap_or(0),
+        None => 0,
+    };
+    let mut request = client.get(url).headers(headers);
+    if let (true, Some(v)) = (offset > 0, &validator) {
+        // the server sends the whole file if it has changed
+        request = request
+            .header(RANGE, format!("bytes={}-", offset))
+            .header(IF_RANGE, v.as_str());
+    }
+    let res = timeout(read_timeout, request.send())
+        .await
+        .map_err(|_| HttpError::Timeout)??;
+    let resumed = offset > 0 && res.status() == StatusCode::PARTIAL_CONTENT;
+    if (offset > 0 && res.status() == StatusCode::RANGE_NOT_SATISFIABLE)
+        || (resumed && range_start(res.headers()) != Some(offset))
+    {
+        // the part file doesn't match the remote file any more, it's downloaded again from the start
+        remove_if_exists(part_file)?;
+        remove_if_exists(&validator_file)?;
+        return Err(HttpError::PartMismatch);
+    }
+    let res = res.error_for_status()?;
+    debug!("{:?}", res.headers());
+    let (mut file, mut downloaded) = if resumed {
+        debug!("resume download of {} from {}", url, offset);
+        (OpenOptions::new().append(true).open(part_file)?, offset)
+    } else {
+        let file = File::create(part_file)?;
+        match res.headers().get(ETAG).or(res.headers().get(LAST_MODIFIED)) {
+            Some(v) => fs::write(&validator_file, v.as_bytes())?,
+            None => remove_if_exists(&validator_file)?,
         }
+        (file, 0)
+    };
+    let total = res.content_length().map(|l| l + downloaded).unwrap_or(0);
+    start_progress(pb, total);
+    pb.set_position(downloaded);
+
+    let mut stream = res.bytes_stream();
+    while let Some(chunk) = timeout(read_timeout, stream.next())
+        .await
+        .map_err(|_| HttpError::Timeout)?
+    {
+        let c = chunk?;
+        file.write_all(&c)?;
+        downloaded += c.len() as u64;
+        trace!("process: {}/{}", downloaded, total);
+        pb.set_position(downloaded);
+    }
+    if downloaded < total {
+        return Err(HttpError::Incomplete(downloaded, total));
     }
     Ok(())
 }
 
+/// e.g. 'tool.tar.gz.part'
+fn part_path(to_file: &Path) -> PathBuf {
+    let mut name = to_file.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    to_file.with_file_name(name)
+}
+
+/// e.g. 'tool.tar.gz.part.validator', the etag or last modified time of the part file.
+fn validator_path(part_file: &Path) -> PathBuf {
+    let mut name = part_file.file_name().unwrap_or_default().to_os_string();
+    name.push(".validator");
+    part_file.with_file_name(name)
+}
+
+/// the first byte in `Content-Range`, e.g. 5 in 'bytes 5-9/10'.
+fn range_start(headers: &HeaderMap) -> Option<u64> {
+    let range = headers.get(CONTENT_RANGE)?.to_str().ok()?;
+    let (start, _) = range.strip_prefix("bytes ")?.split_once('-')?;
+    start.trim().parse().ok()
+}
+
+fn remove_if_exists(file: &Path) -> std::io::Result<()> {
+    match fs::remove_file(file) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// the delay before the nth retry, it's doubled every time.
+fn retry_delay(first: u64, retry: u32) -> Duration {
+    let delay = first.saturating_mul(1 << (retry - 1).min(16));
+    Duration::from_millis(delay.min(MAX_RETRY_DELAY))
+}
+
+/// a bar if the size is known, otherwise a spinner.
+fn start_progress(pb: &ProgressBar, size: u64) {
+    if size > 0 {
+        pb.set_length(size);
+        pb.set_style(ProgressStyle::with_template("{spinner:.green} {prefix:<12} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({eta}) {msg}")
+            .unwrap()
+            .with_key("eta", |state: &ProgressState, w: &mut dyn std::fmt::Write| write!(w, "{:.1}s", state.eta().as_secs_f64()).unwrap())
+            .progress_chars("#>-"));
+    } else {
+        pb.set_style(
+            ProgressStyle::with_template("{spinner:.blue} {prefix:<12} {msg}")
+                .unwrap()
+                .tick_strings(&[
+                    "▹▹▹▹▹",
+                    "▸▹▹▹▹",
+                    "▹▸▹▹▹",
+                    "▹▹▸▹▹",
+                    "▹▹▹▸▹",
+                    "▹▹▹▹▸",
+                    "▪▪▪▪▪",
+                ]),
+        );
+        pb.set_message("Downloading...");
+        pb.enable_steady_tick(std::time::Duration::from_millis(120));
+    }
+}
+
 async fn get_client() -> Result<reqwest::Client> {
-    let client = reqwest::Client::builder().build()?;
-    Ok(client)
+    build_client(network())
 }
 
-#[derive(Debug, Clone)]
-pub enum ProgressInfo {
-    Start(u64),
-    Progress { current: u64, total: Option<u64> },
-    Complete(),
-    Error(String),
+/// a client with the proxy, certificates, user agent and timeouts in settings.
+fn build_client(settings: &NetworkSettings) -> Result<reqwest::Client> {
+    let mut builder = reqwest::Client::builder()
+        .user_agent(settings.user_agent.as_str())
+        .connect_timeout(Duration::from_secs(settings.connect_timeout))
+        .danger_accept_invalid_certs(settings.insecure_skip_verify);
+    if let Some(url) = &settings.proxy {
+        let no_proxy = NoProxy::from_string(&settings.no_proxy.join(","));
+        builder = builder.proxy(Proxy::all(url)?.no_proxy(no_proxy));
+    }
+    for path in &settings.ca_certs {
+        for cert in Certificate::from_pem_bundle(&fs::read(path)?)? {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+    Ok(builder.build()?)
 }
 
 #[derive(Error, Debug)]
@@ -146,6 +286,250 @@ pub enum HttpError {
     Http(#[from] reqwest::Error),
     #[error("file system error: {0}")]
     FileSystem(#[from] std::io::Error),
-    #[error("{0}")]
-    General(String),
+    #[error("no data is received in time")]
+    Timeout,
+    #[error("download is incomplete, {0} of {1} bytes")]
+    Incomplete(u64, u64),
+    #[error("the partly downloaded file doesn't match the remote file")]
+    PartMismatch,
+}
+
+impl HttpError {
+    /// errors of connections and servers might be gone in a retry, but not client errors.
+    fn is_retryable(&self) -> bool {
+        match self {
+            HttpError::Http(e) => match e.status() {
+                Some(status) => {
+                    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+                }
+                None => true,
+            },
+            HttpError::Timeout | HttpError::Incomplete(_, _) | HttpError::PartMismatch => true,
+            HttpError::FileSystem(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn settings() -> NetworkSettings {
+        NetworkSettings {
+            retries: 2,
+            retry_delay: 1,
+            connect_timeout: 5,
+            read_timeout: 5,
+            ..Default::default()
+        }
+    }
+
+    /// serve the responses in order, one connection each, the requests are returned.
+    async fn serve(responses: Vec<&'static str>) -> (String, tokio::task::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/tool.tar.gz", listener.local_addr().unwrap());
+        let handle = tokio::spawn(async move {
+            let mut requests = vec![];
+            for response in responses {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = vec![];
+                let mut buf = [0; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    let n = socket.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                }
+                requests.push(String::from_utf8(request).unwrap().to_lowercase());
+                socket.write_all(response.as_bytes()).await.unwrap();
+                // the connection is dropped, even if the body is not complete
+            }
+            requests
+        });
+        (url, handle)
+    }
+
+    #[tokio::test]
+    async fn test_resume_download() {
+        let (url, server) = serve(vec![
+            "HTTP/1.1 200 OK\r\nContent-Length: 10\r\nAccept-Ranges: bytes\r\nETag: \"v1\"\r\n\r\n01234",
+            "HTTP/1.1 206 Partial Content\r\nContent-Length: 5\r\nContent-Range: bytes 5-9/10\r\n\r\n56789",
+        ])
+        .await;
+        let dir = tempfile::tempdir().unwrap();
+        let to_file = dir.path().join("tool.tar.gz");
+        let pb = ProgressBar::hidden();
+
+        download(&url, &to_file, HeaderMap::new(), &pb, &settings())
+            .await
+            .unwrap();
+
+        assert_eq!("0123456789", fs::read_to_string(&to_file).unwrap());
+        assert!(!part_path(&to_file).exists());
+        assert!(!validator_path(&part_path(&to_file)).exists());
+        assert_eq!(Some(10), pb.length());
+        assert_eq!(10, pb.position());
+        let requests = server.await.unwrap();
+        assert!(!requests[0].contains("range:"));
+        assert!(requests[1].contains("range: bytes=5-\r\n"));
+        assert!(requests[1].contains("if-range: \"v1\"\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_restart_on_range_mismatch() {
+        let (url, server) = serve(vec![
+            "HTTP/1.1 200 OK\r\nContent-Length: 10\r\nLast-Modified: Wed, 01 May 2024 00:00:00 GMT\r\n\r\n01234",
+            "HTTP/1.1 206 Partial Content\r\nContent-Length: 7\r\nContent-Range: bytes 3-9/10\r\n\r\n3456789",
+            "HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\n0123456789",
+        ])
+        .await;
+        let dir = tempfile::tempdir().unwrap();
+        let to_file = dir.path().join("tool.tar.gz");
+
+        download(
+            &url,
+            &to_file,
+            HeaderMap::new(),
+            &ProgressBar::hidden(),
+            &settings(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!("0123456789", fs::read_to_string(&to_file).unwrap());
+        let requests = server.await.unwrap();
+        assert!(requests[1].contains("if-range: wed, 01 may 2024 00:00:00 gmt\r\n"));
+        assert!(!requests[2].contains("range:"));
+    }
+
+    #[test]
+    fn test_range_start() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(None, range_start(&headers));
+        headers.insert(CONTENT_RANGE, "bytes 5-9/10".parse().unwrap());
+        assert_eq!(Some(5), range_start(&headers));
+        headers.insert(CONTENT_RANGE, "bytes */10".parse().unwrap());
+        assert_eq!(None, range_start(&headers));
+    }
+
+    #[tokio::test]
+    async fn test_restart_without_range() {
+        let (url, server) = serve(vec![
+            "HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\n01234",
+            "HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\n0123456789",
+        ])
+        .await;
+        let dir = tempfile::tempdir().unwrap();
+        let to_file = dir.path().join("tool.tar.gz");
+
+        download(
+            &url,
+            &to_file,
+            HeaderMap::new(),
+            &ProgressBar::hidden(),
+            &settings(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!("0123456789", fs::read_to_string(&to_file).unwrap());
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_give_up_after_retries() {
+        let (url, server) = serve(vec![
+            "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\n\r\n",
+            "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\n\r\n",
+            "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\n\r\n",
+        ])
+        .await;
+        let dir = tempfile::tempdir().unwrap();
+        let to_file = dir.path().join("tool.tar.gz");
+
+        let result = download(
+            &url,
+            &to_file,
+            HeaderMap::new(),
+            &ProgressBar::hidden(),
+            &settings(),
+        )
+        .await;
+
+        assert!(result.is_err());
+        assert!(!to_file.exists());
+        assert_eq!(3, server.await.unwrap().len());
+    }
+
+    #[tokio::test]
+    async fn test_no_retry_for_client_error() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/tool.tar.gz")
+            .with_status(404)
+            .expect(1)
+            .create_async()
+            .await;
+        let dir = tempfile::tempdir().unwrap();
+        let url = format!("{}/tool.tar.gz", server.url());
+
+        let result = download(
+            &url,
+            &dir.path().join("tool.tar.gz"),
+            HeaderMap::new(),
+            &ProgressBar::hidden(),
+            &settings(),
+        )
+        .await;
+
+        assert!(result.is_err());
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_proxy_and_user_agent() {
+        let (proxy, server) = serve(vec!["HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\ntool"]).await;
+        let settings = NetworkSettings {
+            proxy: Some(proxy.trim_end_matches("/tool.tar.gz").to_string()),
+            user_agent: "kaleido-test".to_string(),
+            ..settings()
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let to_file = dir.path().join("tool.tar.gz");
+
+        download(
+            "http://example.invalid/tool.tar.gz",
+            &to_file,
+            HeaderMap::new(),
+            &ProgressBar::hidden(),
+            &settings,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!("tool", fs::read_to_string(&to_file).unwrap());
+        let requests = server.await.unwrap();
+        assert!(requests[0].starts_with("get http://example.invalid/tool.tar.gz "));
+        assert!(requests[0].contains("user-agent: kaleido-test\r\n"));
+    }
+
+    #[test]
+    fn test_build_client_with_bad_ca() {
+        let dir = tempfile::tempdir().unwrap();
+        let settings = NetworkSettings {
+            ca_certs: vec![dir.path().join("missing.pem")],
+            ..Default::default()
+        };
+        assert!(build_client(&settings).is_err());
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        assert_eq!(Duration::from_millis(1000), retry_delay(1000, 1));
+        assert_eq!(Duration::from_millis(4000), retry_delay(1000, 3));
+        assert_eq!(
+            Duration::from_millis(MAX_RETRY_DELAY),
+            retry_delay(1000, 10)
+        );
+    }
 }
@@ -1,5 +1,5 @@
-use log::error;
 use serde::{self, Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
 use std::io::Read;
 use std::path::Path;
@@ -24,6 +24,19 @@ pub struct Bindle {
 pub struct BindlePackage {
     pub name: String,
     pub alias: Option<String>,
+    /// an exact version or a semver requirement, e.g. '^0.18', the latest version is installed if it's not specified.
+    pub version: Option<String>,
+    pub channel: Option<Channel>,
+}
+
+/// which releases could be installed, drafts are always ignored.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    #[default]
+    Stable,
+    /// prereleases are also included, e.g. release candidates.
+    Prerelease,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
@@ -39,7 +52,60 @@ pub struct Package {
     /// by default, sys-kaleido tries to find download fiel by 'arch', 'os', and maybe abi(for rust only)
     /// if this is specified, it will be also used when searching remote files.
     pub remote_file_prefix: Option<String>,
+    /// the pattern of the remote file name, it's more precise than `remote_file_prefix`.
+    pub asset_pattern: Option<AssetPattern>,
+    /// tag name of a release, '{version}' and '{name}' will be replaced, e.g. '{name}-v{version}'.
+    /// the tag name is the version, or `v` + version if it's not specified.
+    pub tag_format: Option<String>,
+    /// 'stable' by default, 'prerelease' to install release candidates.
+    pub channel: Option<Channel>,
+    /// options for a platform, the key is a target triple('x86_64-unknown-linux-gnu'),
+    /// arch and os('aarch64-macos'), or os('windows').
+    #[serde(default)]
+    pub platforms: HashMap<String, PlatformOverride>,
     pub github: Option<Github>,
+    pub gitlab: Option<Gitlab>,
+    pub gitea: Option<Gitea>,
+    pub url_template: Option<UrlTemplate>,
+    pub crates_io: Option<CratesIo>,
+    pub binstall: Option<Binstall>,
+}
+
+/// a string is a glob, e.g. 'cpz-{arch}-*', or a regex in a table, e.g. `{ regex = "^cpz-.*" }`.
+/// placeholders '{os}', '{arch}' and '{abi}' match all names of current platform.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum AssetPattern {
+    Glob(String),
+    Regex { regex: String },
+}
+
+/// package options which are different on a platform.
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+pub struct PlatformOverride {
+    pub asset_pattern: Option<AssetPattern>,
+    pub bin_name: Option<String>,
+    pub bin_path: Option<String>,
+}
+
+impl Package {
+    /// apply overrides found by keys from the least specific one, so a more specific key wins,
+    /// the most specific key should be the first.
+    pub fn for_platform(&self, keys: &[String]) -> Package {
+        let mut package = self.clone();
+        for o in keys.iter().rev().filter_map(|k| self.platforms.get(k)) {
+            if o.asset_pattern.is_some() {
+                package.asset_pattern = o.asset_pattern.clone();
+            }
+            if let Some(bin_name) = &o.bin_name {
+                package.bin_name = bin_name.clone();
+            }
+            if o.bin_path.is_some() {
+                package.bin_path = o.bin_path.clone();
+            }
+        }
+        package
+    }
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
@@ -48,6 +114,69 @@ pub struct Github {
     pub repo: String,
 }
 
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Gitlab {
+    /// full project path, e.g. 'gitlab-org/cli'
+    pub project: String,
+    /// host of a self-hosted instance, 'https://gitlab.com' is used if it's not specified.
+    pub host: Option<String>,
+}
+
+/// a Gitea compatible forge, such as Forgejo or Codeberg.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Gitea {
+    /// base url of the instance, e.g. 'https://codeberg.org'
+    pub base_url: String,
+    pub org: String,
+    pub repo: String,
+    /// access token, only needed for private repositories.
+    pub token: Option<String>,
+}
+
+/// download binaries from a predictable url, without any forge api.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct UrlTemplate {
+    /// download url, placeholders '{version}', '{os}', '{arch}', '{abi}' and '{ext}' will be replaced.
+    pub url: String,
+    /// value of '{ext}', 'tar.gz' by default.
+    pub ext: Option<String>,
+    /// value of '{ext}' on Windows, `ext` is used if it's not specified.
+    pub windows_ext: Option<String>,
+    /// all available versions, the first one is the latest.
+    pub versions: Option<Vec<String>>,
+    /// url of a plain text file which only contains the latest version.
+    pub latest_url: Option<String>,
+}
+
+/// a cargo registry which uses sparse index, the source code is downloaded from it and built locally.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct CratesIo {
+    /// crate name, the package name is used if it's not specified.
+    #[serde(rename = "crate")]
+    pub crate_name: Option<String>,
+    /// sparse index url, 'https://index.crates.io/' is used if it's not specified.
+    pub index: Option<String>,
+}
+
+/// cargo-binstall style templates to find the asset and the executable file in it,
+/// see https://github.com/cargo-bins/cargo-binstall/blob/main/SUPPORT.md
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+pub struct Binstall {
+    #[serde(alias = "pkg-url")]
+    pub pkg_url: Option<String>,
+    #[serde(alias = "bin-dir")]
+    pub bin_dir: Option<String>,
+    #[serde(alias = "pkg-fmt")]
+    pub pkg_fmt: Option<String>,
+    /// templates for a target triple, they replace the ones above.
+    #[serde(default)]
+    pub overrides: HashMap<String, Binstall>,
+    /// read `[package.metadata.binstall]` from Cargo.toml in the source archive,
+    /// templates in kaleido.toml have higher priority.
+    #[serde(default)]
+    pub from_source: bool,
+}
+
 type Result<T> = std::result::Result<T, ConfigError>;
 
 impl Kaleido {
@@ -98,3 +227,51 @@ pub enum ConfigError {
     #[error("cannot find kaleido file")]
     NoSuchFile(),
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_for_platform() {
+        let package: Package = toml::from_str(
+            r#"
+            name = "cpz"
+            url = "https://github.com/supercilex/fuc"
+            bin_name = "cpz"
+            asset_pattern = "cpz-{arch}-*"
+            [platforms.windows]
+            bin_name = "cpz-win"
+            [platforms.linux]
+            bin_name = "cpz-linux"
+            bin_path = "linux"
+            [platforms.x86_64-unknown-linux-musl]
+            asset_pattern = { regex = "^cpz-.*-musl$" }
+            bin_path = "bin"
+            "#,
+        )
+        .unwrap();
+
+        let keys = |k: &[&str]| -> Vec<String> { k.iter().map(|s| s.to_string()).collect() };
+        let linux = package.for_platform(&keys(&[
+            "x86_64-unknown-linux-musl",
+            "x86_64-linux",
+            "linux",
+        ]));
+        assert!(matches!(
+            linux.asset_pattern,
+            Some(AssetPattern::Regex { .. })
+        ));
+        assert_eq!(Some("bin".to_string()), linux.bin_path);
+        // merged from the os override
+        assert_eq!("cpz-linux", linux.bin_name);
+
+        let windows = package.for_platform(&keys(&[
+            "x86_64-pc-windows-msvc",
+            "x86_64-windows",
+            "windows",
+        ]));
+        assert!(matches!(windows.asset_pattern, Some(AssetPattern::Glob(_))));
+        assert_eq!("cpz-win", windows.bin_name);
+    }
+}
@@ -0,0 +1,84 @@
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io;
+use std::path::PathBuf;
+use thiserror::Error;
+
+use super::fs::AppDir;
+
+pub const LOCK_FILE: &str = "lock";
+pub const PID_FILE: &str = "lock.pid";
+
+pub type Result<T> = std::result::Result<T, LockError>;
+
+/// an advisory lock on the state directory, so commands which change packages don't run at the same time.
+/// it's released on drop, or when the process exits.
+pub struct StateLock {
+    file: File,
+    pid_file: PathBuf,
+}
+
+impl StateLock {
+    /// lock the state directory, wait for the other process if `wait` is true.
+    pub fn acquire(app_dir: &AppDir, wait: bool) -> Result<Self> {
+        let state_dir = app_dir.get_state_dir();
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(state_dir.join(LOCK_FILE))?;
+        let pid_file = state_dir.join(PID_FILE);
+        match file.try_lock() {
+            Ok(_) => {}
+            Err(TryLockError::WouldBlock) => {
+                let pid = read_pid(&pid_file);
+                if !wait {
+                    return Err(LockError::Locked(pid));
+                }
+                println!("waiting for another sys-kaleido (pid {})", pid);
+                file.lock()?;
+            }
+            Err(TryLockError::Error(e)) => return Err(LockError::Io(e)),
+        }
+        fs::write(&pid_file, std::process::id().to_string())?;
+        Ok(StateLock { file, pid_file })
+    }
+}
+
+impl Drop for StateLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.pid_file);
+        let _ = self.file.unlock();
+    }
+}
+
+fn read_pid(pid_file: &PathBuf) -> String {
+    fs::read_to_string(pid_file)
+        .map(|s| s.trim().to_string())
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+#[derive(Error, Debug)]
+pub enum LockError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("another sys-kaleido is running (pid {0}), use --wait to wait for it")]
+    Locked(String),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_state_lock() {
+        let dir = tempfile::tempdir().unwrap();
+        let app_dir = AppDir::from_home(dir.path().join("home")).unwrap();
+        let lock = StateLock::acquire(&app_dir, false).unwrap();
+        match StateLock::acquire(&app_dir, false) {
+            Err(LockError::Locked(pid)) => assert_eq!(std::process::id().to_string(), pid),
+            _ => panic!("the state directory should be locked"),
+        }
+        drop(lock);
+        assert!(StateLock::acquire(&app_dir, false).is_ok());
+    }
+}
@@ -1,6 +1,10 @@
 pub mod args;
+pub mod cache;
 pub mod fs;
 pub mod http;
 pub mod kaleido;
+pub mod lock;
 pub mod logger;
+pub mod settings;
+pub mod state;
 pub mod global_input;
\ No newline at end of file
@@ -0,0 +1,188 @@
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::kaleido::ConfigError;
+use super::logger::error;
+
+pub const SETTINGS_FILE: &str = "settings.toml";
+
+type Result<T> = std::result::Result<T, ConfigError>;
+
+/// user settings in `~/.sys-kaleido/settings.toml`, all fields are optional.
+#[derive(Deserialize, Debug, Default, PartialEq)]
+pub struct Settings {
+    #[serde(default)]
+    pub prune: PruneSettings,
+    #[serde(default)]
+    pub cache: CacheSettings,
+    #[serde(default)]
+    pub network: NetworkSettings,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct PruneSettings {
+    /// prune old versions after `update`
+    #[serde(default)]
+    pub auto: bool,
+    /// how many newest versions are kept
+    #[serde(default = "default_keep")]
+    pub keep: usize,
+}
+
+impl Default for PruneSettings {
+    fn default() -> Self {
+        PruneSettings {
+            auto: false,
+            keep: default_keep(),
+        }
+    }
+}
+
+pub fn default_keep() -> usize {
+    2
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct CacheSettings {
+    /// max size of downloaded files in MiB, the least recently used ones are removed first, 0 disables the cache
+    #[serde(default = "default_max_size")]
+    pub max_size: u64,
+}
+
+impl Default for CacheSettings {
+    fn default() -> Self {
+        CacheSettings {
+            max_size: default_max_size(),
+        }
+    }
+}
+
+pub fn default_max_size() -> u64 {
+    1024
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct NetworkSettings {
+    /// how many times a failed download is retried
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+    /// milliseconds to wait before the first retry, it's doubled for every retry
+    #[serde(default = "default_retry_delay")]
+    pub retry_delay: u64,
+    /// seconds to wait for a connection
+    #[serde(default = "default_connect_timeout")]
+    pub connect_timeout: u64,
+    /// seconds to wait for any data from the server
+    #[serde(default = "default_read_timeout")]
+    pub read_timeout: u64,
+    /// proxy of all requests, e.g. 'http://proxy.example.com:8080'
+    pub proxy: Option<String>,
+    /// hosts which are connected without the proxy, e.g. 'localhost', '.example.com' or '10.0.0.0/8'
+    #[serde(default)]
+    pub no_proxy: Vec<String>,
+    /// PEM files of certificates which are trusted besides system ones
+    #[serde(default)]
+    pub ca_certs: Vec<PathBuf>,
+    /// don't verify certificates, only for testing
+    #[serde(default)]
+    pub insecure_skip_verify: bool,
+    #[serde(default = "default_user_agent")]
+    pub user_agent: String,
+}
+
+impl Default for NetworkSettings {
+    fn default() -> Self {
+        NetworkSettings {
+            retries: default_retries(),
+            retry_delay: default_retry_delay(),
+            connect_timeout: default_connect_timeout(),
+            read_timeout: default_read_timeout(),
+            proxy: None,
+            no_proxy: vec![],
+            ca_certs: vec![],
+            insecure_skip_verify: false,
+            user_agent: default_user_agent(),
+        }
+    }
+}
+
+pub fn default_retries() -> u32 {
+    3
+}
+
+pub fn default_retry_delay() -> u64 {
+    1000
+}
+
+pub fn default_connect_timeout() -> u64 {
+    10
+}
+
+pub fn default_read_timeout() -> u64 {
+    30
+}
+
+pub fn default_user_agent() -> String {
+    "sys-kaleido".to_string()
+}
+
+impl Settings {
+    /// default settings are used if the file doesn't exist.
+    pub fn load(home_dir: &Path) -> Result<Self> {
+        let path = home_dir.join(SETTINGS_FILE);
+        if !path.is_file() {
+            return Ok(Settings::default());
+        }
+        let content = fs::read_to_string(path)?;
+        Ok(toml::from_str(&content)?)
+    }
+
+    /// default settings are used if the file is broken.
+    pub fn load_or_default(home_dir: &Path) -> Self {
+        match Settings::load(home_dir) {
+            Ok(s) => s,
+            Err(e) => {
+                error!("Failed to load settings: {}", e);
+                Settings::default()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_load_settings() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(Settings::default(), Settings::load(dir.path()).unwrap());
+
+        fs::write(dir.path().join(SETTINGS_FILE), "[prune]\nauto = true\n").unwrap();
+        let settings = Settings::load(dir.path()).unwrap();
+        assert!(settings.prune.auto);
+        assert_eq!(2, settings.prune.keep);
+        assert_eq!(1024, settings.cache.max_size);
+
+        fs::write(dir.path().join(SETTINGS_FILE), "[cache]\nmax_size = 0\n").unwrap();
+        assert_eq!(0, Settings::load(dir.path()).unwrap().cache.max_size);
+
+        fs::write(dir.path().join(SETTINGS_FILE), "[network]\nretries = 5\n").unwrap();
+        let network = Settings::load(dir.path()).unwrap().network;
+        assert_eq!(5, network.retries);
+        assert_eq!(30, network.read_timeout);
+        assert_eq!("sys-kaleido", network.user_agent);
+
+        fs::write(
+            dir.path().join(SETTINGS_FILE),
+            "[network]\nproxy = \"http://proxy:8080\"\nno_proxy = [\"localhost\"]\nca_certs = [\"/etc/ca.pem\"]\n",
+        )
+        .unwrap();
+        let network = Settings::load(dir.path()).unwrap().network;
+        assert_eq!(Some("http://proxy:8080"), network.proxy.as_deref());
+        assert_eq!(vec!["localhost"], network.no_proxy);
+        assert_eq!(vec![PathBuf::from("/etc/ca.pem")], network.ca_certs);
+        assert!(!network.insecure_skip_verify);
+    }
+}
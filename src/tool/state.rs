@@ -0,0 +1,357 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+use super::fs::AppDir;
+
+pub const PINS_FILE: &str = "pins.toml";
+pub const RECEIPTS_DIR: &str = "receipts";
+/// created after receipts are migrated from links, so it's done only once.
+pub const MIGRATED_FILE: &str = "receipts.migrated";
+/// how many previously active versions are kept.
+const MAX_HISTORY: usize = 10;
+
+pub type Result<T> = std::result::Result<T, StateError>;
+
+/// packages which should not be updated, saved in `state/pins.toml`.
+#[derive(Deserialize, Serialize, Debug, Default, PartialEq)]
+pub struct Pins {
+    #[serde(default)]
+    pub packages: BTreeMap<String, Pin>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+pub struct Pin {
+    /// the version to stay on, any installed version is kept if it's empty.
+    pub version: Option<String>,
+}
+
+impl Pins {
+    pub fn load(app_dir: &AppDir) -> Result<Self> {
+        let file = app_dir.get_state_dir().join(PINS_FILE);
+        if !file.exists() {
+            return Ok(Pins::default());
+        }
+        let content = fs::read_to_string(file)?;
+        Ok(toml::from_str(&content)?)
+    }
+
+    pub fn save(&self, app_dir: &AppDir) -> Result<()> {
+        let content = toml::to_string(self)?;
+        fs::write(app_dir.get_state_dir().join(PINS_FILE), content)?;
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Pin> {
+        self.packages.get(name)
+    }
+}
+
+impl Pin {
+    /// e.g. 'pinned' or 'pinned at 1.0.0'.
+    pub fn describe(&self) -> String {
+        match &self.version {
+            Some(v) => format!("pinned at {}", v),
+            None => "pinned".to_string(),
+        }
+    }
+}
+
+/// how a package was built.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BuildKind {
+    /// a released binary
+    Binary,
+    /// built from source code
+    Source,
+}
+
+/// an installed version of a package.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Receipt {
+    pub version: String,
+    pub bin_name: String,
+    /// where the asset or source code was downloaded from, unknown for migrated receipts.
+    pub source_url: Option<String>,
+    pub asset_name: Option<String>,
+    /// sha256 of the downloaded file
+    pub sha256: Option<String>,
+    /// aliases linked to this version
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    /// seconds since unix epoch
+    pub installed_at: u64,
+    pub build: Option<BuildKind>,
+}
+
+/// a version which was active before.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Activation {
+    pub version: String,
+    /// aliases and the versions they linked to
+    #[serde(default)]
+    pub aliases: BTreeMap<String, String>,
+}
+
+/// install receipts of a package, saved in `state/receipts/<name>.toml`.
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+pub struct PackageReceipts {
+    pub name: String,
+    /// the version linked to the bin directory
+    pub current: Option<String>,
+    #[serde(default)]
+    pub versions: Vec<Receipt>,
+    /// previously active versions, the latest one is the last.
+    #[serde(default)]
+    pub history: Vec<Activation>,
+}
+
+impl PackageReceipts {
+    /// receipts of the package, it's empty if nothing is recorded.
+    pub fn load(app_dir: &AppDir, name: &str) -> Result<Self> {
+        let file = receipt_file(app_dir, name);
+        if !file.exists() {
+            return Ok(PackageReceipts {
+                name: name.to_string(),
+                ..Default::default()
+            });
+        }
+        let content = fs::read_to_string(file)?;
+        Ok(toml::from_str(&content)?)
+    }
+
+    /// receipts of all packages.
+    pub fn load_all(app_dir: &AppDir) -> Result<Vec<Self>> {
+        let dir = receipts_dir(app_dir);
+        let mut all = vec![];
+        if !dir.is_dir() {
+            return Ok(all);
+        }
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|e| e == "toml") {
+                let content = fs::read_to_string(path)?;
+                all.push(toml::from_str(&content)?);
+            }
+        }
+        all.sort_by(|a: &PackageReceipts, b| a.name.cmp(&b.name));
+        Ok(all)
+    }
+
+    /// save the receipts, the file is deleted if there is no version.
+    pub fn save(&self, app_dir: &AppDir) -> Result<()> {
+        let file = receipt_file(app_dir, &self.name);
+        if self.versions.is_empty() {
+            if file.exists() {
+                fs::remove_file(file)?;
+            }
+            return Ok(());
+        }
+        fs::create_dir_all(receipts_dir(app_dir))?;
+        fs::write(file, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// delete the receipts of the package with its history, e.g. after it's uninstalled.
+    pub fn remove(app_dir: &AppDir, name: &str) -> Result<()> {
+        let file = receipt_file(app_dir, name);
+        if file.exists() {
+            fs::remove_file(file)?;
+        }
+        Ok(())
+    }
+
+    /// add or replace the receipt of a version, it becomes the current version,
+    /// and its aliases are removed from other versions.
+    pub fn record(&mut self, receipt: Receipt) {
+        self.activate(&receipt.version);
+        for r in self.versions.iter_mut() {
+            r.aliases.retain(|a| !receipt.aliases.contains(a));
+        }
+        self.insert(receipt);
+    }
+
+    /// make the version current, the previous one is saved in history with its aliases.
+    pub fn activate(&mut self, version: &str) {
+        if let Some(current) = &self.current {
+            if current != version {
+                self.history.push(Activation {
+                    version: current.clone(),
+                    aliases: self.alias_map(),
+                });
+                if self.history.len() > MAX_HISTORY {
+                    self.history.remove(0);
+                }
+            }
+        }
+        self.current = Some(version.to_string());
+    }
+
+    /// aliases and the versions they link to.
+    pub fn alias_map(&self) -> BTreeMap<String, String> {
+        self.versions
+            .iter()
+            .flat_map(|r| r.aliases.iter().map(|a| (a.clone(), r.version.clone())))
+            .collect()
+    }
+
+    /// link aliases to versions as the map says, aliases of unknown versions are dropped.
+    pub fn restore_aliases(&mut self, aliases: &BTreeMap<String, String>) {
+        for r in self.versions.iter_mut() {
+            r.aliases = aliases
+                .iter()
+                .filter(|(_, v)| **v == r.version)
+                .map(|(a, _)| a.clone())
+                .collect();
+        }
+    }
+
+    /// add or replace the receipt of a version without changing the current version.
+    pub fn insert(&mut self, receipt: Receipt) {
+        self.versions.retain(|r| r.version != receipt.version);
+        self.versions.push(receipt);
+    }
+
+    pub fn current_receipt(&self) -> Option<&Receipt> {
+        let current = self.current.as_ref()?;
+        self.versions.iter().find(|r| &r.version == current)
+    }
+
+    /// all aliases are linked to the given version now.
+    pub fn move_aliases(&mut self, version: &str) {
+        let mut aliases = vec![];
+        for r in self.versions.iter_mut() {
+            aliases.append(&mut r.aliases);
+        }
+        if let Some(r) = self.versions.iter_mut().find(|r| r.version == version) {
+            r.aliases = aliases;
+        }
+    }
+}
+
+/// true if receipts have been migrated from links, receipts of new installs don't count.
+pub fn is_migrated(app_dir: &AppDir) -> bool {
+    app_dir.get_state_dir().join(MIGRATED_FILE).is_file()
+}
+
+pub fn mark_migrated(app_dir: &AppDir) -> Result<()> {
+    fs::write(
+        app_dir.get_state_dir().join(MIGRATED_FILE),
+        unix_now().to_string(),
+    )?;
+    Ok(())
+}
+
+pub fn receipts_dir(app_dir: &AppDir) -> PathBuf {
+    app_dir.get_state_dir().join(RECEIPTS_DIR)
+}
+
+fn receipt_file(app_dir: &AppDir, name: &str) -> PathBuf {
+    receipts_dir(app_dir).join(format!("{}.toml", name))
+}
+
+/// seconds since unix epoch.
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[derive(Error, Debug)]
+pub enum StateError {
+    #[error("file system error: {0}")]
+    Fs(#[from] std::io::Error),
+    #[error("state file error: {0}")]
+    Read(#[from] toml::de::Error),
+    #[error("state file error: {0}")]
+    Write(#[from] toml::ser::Error),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pins() {
+        let dir = tempfile::tempdir().unwrap();
+        let app_dir = AppDir::from_home(dir.path().join("home")).unwrap();
+        assert_eq!(Pins::default(), Pins::load(&app_dir).unwrap());
+
+        let mut pins = Pins::default();
+        pins.packages.insert(
+            "eza".to_string(),
+            Pin {
+                version: Some("0.18.0".to_string()),
+            },
+        );
+        pins.packages
+            .insert("fd".to_string(), Pin { version: None });
+        pins.save(&app_dir).unwrap();
+
+        let pins = Pins::load(&app_dir).unwrap();
+        assert_eq!("pinned at 0.18.0", pins.get("eza").unwrap().describe());
+        assert_eq!("pinned", pins.get("fd").unwrap().describe());
+        assert!(pins.get("rg").is_none());
+    }
+
+    fn receipt(version: &str, aliases: &[&str]) -> Receipt {
+        Receipt {
+            version: version.to_string(),
+            bin_name: "eza".to_string(),
+            source_url: Some(format!("https://example.com/eza-{}.tar.gz", version)),
+            asset_name: Some(format!("eza-{}.tar.gz", version)),
+            sha256: Some("abc".to_string()),
+            aliases: aliases.iter().map(|a| a.to_string()).collect(),
+            installed_at: unix_now(),
+            build: Some(BuildKind::Binary),
+        }
+    }
+
+    #[test]
+    fn test_receipts() {
+        let dir = tempfile::tempdir().unwrap();
+        let app_dir = AppDir::from_home(dir.path().join("home")).unwrap();
+
+        let mut receipts = PackageReceipts::load(&app_dir, "eza").unwrap();
+        assert!(receipts.versions.is_empty());
+        receipts.record(receipt("0.18.0", &["ls", "ll"]));
+        receipts.record(receipt("0.19.0", &["ll"]));
+        receipts.save(&app_dir).unwrap();
+        // new receipts are not a migration
+        assert!(!is_migrated(&app_dir));
+        mark_migrated(&app_dir).unwrap();
+        assert!(is_migrated(&app_dir));
+
+        let mut receipts = PackageReceipts::load(&app_dir, "eza").unwrap();
+        assert_eq!(Some("0.19.0"), receipts.current.as_deref());
+        assert_eq!(vec!["ls"], receipts.versions[0].aliases);
+        assert_eq!(vec!["ll"], receipts.current_receipt().unwrap().aliases);
+
+        receipts.move_aliases("0.19.0");
+        assert!(receipts.versions[0].aliases.is_empty());
+        assert_eq!(2, receipts.current_receipt().unwrap().aliases.len());
+        assert_eq!(1, PackageReceipts::load_all(&app_dir).unwrap().len());
+
+        assert_eq!(1, receipts.history.len());
+        assert_eq!("0.18.0", receipts.history[0].version);
+        assert_eq!(
+            Some("0.18.0"),
+            receipts.history[0].aliases.get("ll").map(|s| s.as_str())
+        );
+
+        receipts.activate("0.18.0");
+        assert_eq!("0.19.0", receipts.history[1].version);
+        receipts.restore_aliases(&receipts.history[0].aliases.clone());
+        assert_eq!(vec!["ll", "ls"], receipts.versions[0].aliases);
+        assert!(receipts.versions[1].aliases.is_empty());
+
+        receipts.versions.clear();
+        receipts.save(&app_dir).unwrap();
+        assert!(PackageReceipts::load_all(&app_dir).unwrap().is_empty());
+    }
+}